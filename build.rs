@@ -11,15 +11,15 @@ compile_error! {
     "`backtrace` feature without `std` feature is not supported"
 }
 
-// This code exercises the surface area that we expect of the std Backtrace
-// type. If the current toolchain is able to compile it, we go ahead and use
-// backtrace in anyhow.
+// This code exercises the surface area that we expect of the nightly generic
+// member access API. If the current toolchain is able to compile it, we go
+// ahead and use it in anyhow to forward backtraces provided by the underlying
+// error.
 const PROBE: &str = r#"
-    #![feature(error_generic_member_access, provide_any)]
+    #![feature(error_generic_member_access)]
 
-    use std::any::{Demand, Provider};
     use std::backtrace::{Backtrace, BacktraceStatus};
-    use std::error::Error;
+    use std::error::{self, Error, Request};
     use std::fmt::{self, Display};
 
     #[derive(Debug)]
@@ -34,17 +34,11 @@ const PROBE: &str = r#"
     }
 
     impl Error for E {
-        fn provide<'a>(&'a self, demand: &mut Demand<'a>) {
-            demand.provide_ref(&self.backtrace);
+        fn provide<'a>(&'a self, request: &mut Request<'a>) {
+            request.provide_ref(&self.backtrace);
         }
     }
 
-    struct P;
-
-    impl Provider for P {
-        fn provide<'a>(&'a self, _demand: &mut Demand<'a>) {}
-    }
-
     const _: fn() = || {
         let backtrace: Backtrace = Backtrace::capture();
         let status: BacktraceStatus = backtrace.status();
@@ -53,22 +47,47 @@ const PROBE: &str = r#"
         }
     };
 
-    const _: fn(&dyn Error) -> Option<&Backtrace> = |err| err.request_ref::<Backtrace>();
+    const _: fn(&dyn Error) -> Option<&Backtrace> = |err| error::request_ref::<Backtrace>(err);
 "#;
 
 fn main() {
+    let mut error_generic_member_access = false;
     if cfg!(feature = "std") {
         match compile_probe() {
-            Some(status) if status.success() => println!("cargo:rustc-cfg=backtrace"),
+            Some(status) if status.success() => {
+                println!("cargo:rustc-cfg=error_generic_member_access");
+                error_generic_member_access = true;
+            }
             _ => {}
         }
     }
 
-    let rustc = match rustc_minor_version() {
+    let rustc = rustc_minor_version();
+
+    // std::backtrace::Backtrace is stable since Rust 1.65.
+    let stable_backtrace = rustc.map_or(false, |rustc| rustc >= 65);
+    if cfg!(feature = "std") && (stable_backtrace || error_generic_member_access) {
+        println!("cargo:rustc-cfg=std_backtrace");
+    }
+
+    let rustc = match rustc {
         Some(rustc) => rustc,
         None => return,
     };
 
+    if rustc >= 80 {
+        println!("cargo:rustc-check-cfg=cfg(anyhow_no_fmt_arguments_as_str)");
+        println!("cargo:rustc-check-cfg=cfg(anyhow_no_ptr_addr_of)");
+        println!("cargo:rustc-check-cfg=cfg(anyhow_no_track_caller)");
+        println!("cargo:rustc-check-cfg=cfg(doc_cfg)");
+        println!("cargo:rustc-check-cfg=cfg(error_generic_member_access)");
+        println!("cargo:rustc-check-cfg=cfg(std_backtrace)");
+    }
+
+    if rustc < 46 {
+        println!("cargo:rustc-cfg=anyhow_no_track_caller");
+    }
+
     if rustc < 51 {
         println!("cargo:rustc-cfg=anyhow_no_ptr_addr_of");
     }
@@ -0,0 +1,22 @@
+use crate::error::AggregateError;
+use crate::StdError;
+use core::fmt::{self, Debug, Display};
+
+impl Debug for AggregateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Error")
+            .field("errors", &self.errors)
+            .finish()
+    }
+}
+
+impl Display for AggregateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.errors.len() {
+            1 => f.write_str("1 error occurred"),
+            n => write!(f, "{} errors occurred", n),
+        }
+    }
+}
+
+impl StdError for AggregateError {}
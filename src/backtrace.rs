@@ -1,89 +1,345 @@
-#[cfg(backtrace)]
+#[cfg(std_backtrace)]
 pub(crate) use std::backtrace::{Backtrace, BacktraceStatus};
 
-#[cfg(all(not(backtrace), feature = "backtrace"))]
-pub(crate) use self::capture::{Backtrace, BacktraceStatus};
+#[cfg(all(not(std_backtrace), feature = "backtrace"))]
+pub(crate) use self::capture::BacktraceStatus;
+#[cfg(feature = "backtrace")]
+pub(crate) use self::capture::{BytesOrWide, Inner};
+#[cfg(all(not(std_backtrace), feature = "backtrace"))]
+pub(crate) use crate::Backtrace;
 
-#[cfg(not(any(backtrace, feature = "backtrace")))]
-pub(crate) enum Backtrace {}
+// The backtrace that a new error keeps. With the "backtrace" feature, the
+// frames are captured in place of the standard library's backtrace, which
+// does not give access to them, so that the stack is only walked once; the
+// standard library's one is then only there for a backtrace given to the
+// error with with_backtrace. Without std_backtrace the backtrace is the
+// crate's own type and has the frames already.
+#[cfg(any(std_backtrace, feature = "backtrace"))]
+pub(crate) struct Captured {
+    pub backtrace: Backtrace,
+    #[cfg(all(std_backtrace, feature = "backtrace"))]
+    pub frames: crate::Backtrace,
+}
+
+#[cfg(not(any(std_backtrace, feature = "backtrace")))]
+pub(crate) enum Captured {}
+
+#[cfg(any(std_backtrace, feature = "backtrace"))]
+impl Captured {
+    // A backtrace whose frames are not known unless it is the crate's own
+    // type, such as one captured elsewhere.
+    pub(crate) fn new(backtrace: Backtrace) -> Self {
+        Captured {
+            backtrace,
+            #[cfg(all(std_backtrace, feature = "backtrace"))]
+            frames: crate::Backtrace::disabled(),
+        }
+    }
+
+    pub(crate) fn disabled() -> Self {
+        Captured::new(Backtrace::disabled())
+    }
 
-#[cfg(backtrace)]
+    #[cfg(feature = "backtrace")]
+    pub(crate) fn frames(&self) -> &crate::Backtrace {
+        #[cfg(std_backtrace)]
+        return &self.frames;
+        #[cfg(not(std_backtrace))]
+        return &self.backtrace;
+    }
+}
+
+#[cfg(std_backtrace)]
 macro_rules! impl_backtrace {
     () => {
         std::backtrace::Backtrace
     };
 }
 
-#[cfg(all(not(backtrace), feature = "backtrace"))]
+#[cfg(all(not(std_backtrace), feature = "backtrace"))]
 macro_rules! impl_backtrace {
     () => {
-        impl core::fmt::Debug + core::fmt::Display
+        crate::Backtrace
     };
 }
 
-#[cfg(any(backtrace, feature = "backtrace"))]
+#[cfg(any(std_backtrace, feature = "backtrace"))]
 macro_rules! backtrace {
     () => {
-        Some(crate::backtrace::Backtrace::capture())
+        Some(crate::backtrace::capture())
     };
 }
 
-#[cfg(not(any(backtrace, feature = "backtrace")))]
+#[cfg(not(any(std_backtrace, feature = "backtrace")))]
 macro_rules! backtrace {
     () => {
         None
     };
 }
 
-#[cfg(backtrace)]
+// An error that is not supposed to capture a backtrace still needs one, for
+// Error::backtrace to return.
+#[cfg(any(std_backtrace, feature = "backtrace"))]
+macro_rules! disabled_backtrace {
+    () => {
+        Some(crate::backtrace::Captured::disabled())
+    };
+}
+
+#[cfg(not(any(std_backtrace, feature = "backtrace")))]
+macro_rules! disabled_backtrace {
+    () => {
+        None
+    };
+}
+
+#[cfg(error_generic_member_access)]
 macro_rules! backtrace_if_absent {
     ($err:expr) => {
-        match ($err as &dyn std::error::Error).request_ref::<std::backtrace::Backtrace>() {
+        backtrace_if_absent!($err, backtrace!())
+    };
+    ($err:expr, $backtrace:expr) => {
+        match std::error::request_ref::<std::backtrace::Backtrace>($err as &dyn std::error::Error) {
             Some(_) => None,
-            None => backtrace!(),
+            None => $backtrace,
         }
     };
 }
 
-#[cfg(all(feature = "std", not(backtrace), feature = "backtrace"))]
+#[cfg(all(
+    feature = "std",
+    not(error_generic_member_access),
+    any(std_backtrace, feature = "backtrace")
+))]
 macro_rules! backtrace_if_absent {
     ($err:expr) => {
         backtrace!()
     };
+    ($err:expr, $backtrace:expr) => {
+        $backtrace
+    };
 }
 
-#[cfg(all(feature = "std", not(backtrace), not(feature = "backtrace")))]
+#[cfg(all(feature = "std", not(std_backtrace), not(feature = "backtrace")))]
 macro_rules! backtrace_if_absent {
-    ($err:expr) => {
+    ($err:expr $(, $backtrace:expr)?) => {
         None
     };
 }
 
-#[cfg(all(not(backtrace), feature = "backtrace"))]
+// Captures a backtrace for a new error, if the backtrace policy allows it.
+#[cfg(any(std_backtrace, feature = "backtrace"))]
+#[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+pub(crate) fn capture() -> Captured {
+    if !self::policy::allows() {
+        return Captured::disabled();
+    }
+
+    #[cfg(all(std_backtrace, feature = "backtrace"))]
+    return Captured {
+        backtrace: Backtrace::disabled(),
+        frames: crate::Backtrace::capture(),
+    };
+
+    #[cfg(not(all(std_backtrace, feature = "backtrace")))]
+    return Captured::new(Backtrace::capture());
+}
+
+#[cfg(all(feature = "std", any(std_backtrace, feature = "backtrace")))]
+pub(crate) use self::policy::set as set_policy;
+
+#[cfg(all(feature = "std", not(std_backtrace), not(feature = "backtrace")))]
+pub(crate) fn set_policy(policy: crate::BacktracePolicy) {
+    let _ = policy;
+}
+
+#[cfg(any(std_backtrace, feature = "backtrace"))]
+mod policy {
+    use crate::BacktracePolicy;
+    use core::ptr;
+    use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+    use std::collections::HashSet;
+    use std::sync::{Mutex, PoisonError};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    const ALWAYS: usize = 0;
+    const NEVER: usize = 1;
+    const SAMPLED: usize = 2;
+    const ONCE_PER_CALL_SITE: usize = 3;
+
+    // File, line and column.
+    type CallSite = (&'static str, u32, u32);
+
+    static POLICY: AtomicUsize = AtomicUsize::new(ALWAYS);
+    static RATE: AtomicUsize = AtomicUsize::new(0);
+
+    pub(crate) fn set(policy: BacktracePolicy) {
+        let policy = match policy {
+            BacktracePolicy::Always => ALWAYS,
+            BacktracePolicy::Never => NEVER,
+            BacktracePolicy::Sampled(per_second) => {
+                RATE.store(per_second as usize, Ordering::Relaxed);
+                SAMPLED
+            }
+            BacktracePolicy::OncePerCallSite => ONCE_PER_CALL_SITE,
+        };
+        POLICY.store(policy, Ordering::Relaxed);
+    }
+
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    pub(crate) fn allows() -> bool {
+        match POLICY.load(Ordering::Relaxed) {
+            ALWAYS => true,
+            NEVER => false,
+            SAMPLED => sample(RATE.load(Ordering::Relaxed)),
+            _ => first_at_call_site(),
+        }
+    }
+
+    // Allows the first `rate` backtraces of every second of the system clock.
+    // Threads racing at the turn of a second may take a few more.
+    fn sample(rate: usize) -> bool {
+        static SECOND: AtomicUsize = AtomicUsize::new(0);
+        static TAKEN: AtomicUsize = AtomicUsize::new(0);
+
+        let now = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(elapsed) => elapsed.as_secs() as usize,
+            Err(_) => 0,
+        };
+        let second = SECOND.load(Ordering::Relaxed);
+        if second != now
+            && SECOND
+                .compare_exchange(second, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            TAKEN.store(0, Ordering::Relaxed);
+        }
+        TAKEN.fetch_add(1, Ordering::Relaxed) < rate
+    }
+
+    #[cfg(not(anyhow_no_track_caller))]
+    #[track_caller]
+    fn first_at_call_site() -> bool {
+        let location = core::panic::Location::caller();
+        let key = location as *const _ as usize;
+        let sites = sites();
+        if sites.seen(key) {
+            return false;
+        }
+        let site = (location.file(), location.line(), location.column());
+        let first = sites
+            .set
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(site);
+        sites.publish(key);
+        first
+    }
+
+    // Without track_caller the call site is unknown.
+    #[cfg(anyhow_no_track_caller)]
+    fn first_at_call_site() -> bool {
+        true
+    }
+
+    const SEEN: usize = 256;
+
+    #[cfg_attr(anyhow_no_track_caller, allow(dead_code))]
+    struct CallSites {
+        // Addresses of the Locations of call sites already seen, in an open
+        // addressing table that is read without the lock. A call site may
+        // have more than one Location, so a miss here is decided by the set.
+        seen: Vec<AtomicUsize>,
+        set: Mutex<HashSet<CallSite>>,
+    }
+
+    #[cfg_attr(anyhow_no_track_caller, allow(dead_code))]
+    impl CallSites {
+        fn slots(&self, key: usize) -> impl Iterator<Item = &AtomicUsize> {
+            let start = (key >> 3) % SEEN;
+            self.seen[start..].iter().chain(&self.seen[..start])
+        }
+
+        fn seen(&self, key: usize) -> bool {
+            for slot in self.slots(key) {
+                match slot.load(Ordering::Relaxed) {
+                    0 => return false,
+                    seen if seen == key => return true,
+                    _ => {}
+                }
+            }
+            false
+        }
+
+        // Once the table is full, further call sites only go by the set.
+        fn publish(&self, key: usize) {
+            for slot in self.slots(key) {
+                match slot.compare_exchange(0, key, Ordering::Relaxed, Ordering::Relaxed) {
+                    Ok(_) => return,
+                    Err(seen) if seen == key => return,
+                    Err(_) => {}
+                }
+            }
+        }
+    }
+
+    #[cfg_attr(anyhow_no_track_caller, allow(dead_code))]
+    fn sites() -> &'static CallSites {
+        static SITES: AtomicPtr<CallSites> = AtomicPtr::new(ptr::null_mut());
+
+        let sites = SITES.load(Ordering::Acquire);
+        if !sites.is_null() {
+            return unsafe { &*sites };
+        }
+        let new = Box::into_raw(Box::new(CallSites {
+            seen: (0..SEEN).map(|_| AtomicUsize::new(0)).collect(),
+            set: Mutex::new(HashSet::new()),
+        }));
+        match SITES.compare_exchange(ptr::null_mut(), new, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => unsafe { &*new },
+            Err(sites) => {
+                // Another thread got there first.
+                drop(unsafe { Box::from_raw(new) });
+                unsafe { &*sites }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "backtrace")]
 mod capture {
-    use backtrace::{BacktraceFmt, BytesOrWideString, Frame, PrintFmt, SymbolName};
+    use crate::frames::Part;
+    use crate::{
+        Backtrace, BacktraceFrame, BacktraceId, BacktraceSymbol, Frames, UnresolvedBacktrace,
+    };
+    use backtrace::{BacktraceFmt, BytesOrWideString, PrintFmt, SymbolName};
     use core::cell::UnsafeCell;
+    use core::ffi::c_void;
     use core::fmt::{self, Debug, Display};
-    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::hash::{Hash, Hasher};
+    use core::ptr;
+    use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
     use std::borrow::Cow;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::HashMap;
     use std::env;
     use std::path::{self, Path, PathBuf};
-    use std::sync::Once;
-
-    pub(crate) struct Backtrace {
-        inner: Inner,
-    }
+    use std::sync::{Arc, Mutex, Once, PoisonError, Weak};
 
+    #[cfg(not(std_backtrace))]
     pub(crate) enum BacktraceStatus {
         Unsupported,
         Disabled,
         Captured,
     }
 
-    enum Inner {
+    pub(crate) enum Inner {
         Unsupported,
         Disabled,
-        Captured(LazilyResolvedCapture),
+        Captured {
+            capture: Arc<LazilyResolvedCapture>,
+            first: bool,
+        },
     }
 
     struct Capture {
@@ -92,21 +348,35 @@ mod capture {
         frames: Vec<BacktraceFrame>,
     }
 
-    struct BacktraceFrame {
-        frame: Frame,
-        symbols: Vec<BacktraceSymbol>,
+    pub(crate) enum BytesOrWide {
+        Bytes(Vec<u8>),
+        Wide(Vec<u16>),
     }
 
-    struct BacktraceSymbol {
-        name: Option<Vec<u8>>,
-        filename: Option<BytesOrWide>,
-        lineno: Option<u32>,
-        colno: Option<u32>,
-    }
+    impl BytesOrWide {
+        fn from_path(path: &Path) -> Self {
+            #[cfg(unix)]
+            {
+                use std::os::unix::ffi::OsStrExt;
+                BytesOrWide::Bytes(path.as_os_str().as_bytes().to_vec())
+            }
+            #[cfg(windows)]
+            {
+                use std::os::windows::ffi::OsStrExt;
+                BytesOrWide::Wide(path.as_os_str().encode_wide().collect())
+            }
+            #[cfg(not(any(unix, windows)))]
+            {
+                BytesOrWide::Bytes(path.to_string_lossy().into_owned().into_bytes())
+            }
+        }
 
-    enum BytesOrWide {
-        Bytes(Vec<u8>),
-        Wide(Vec<u16>),
+        fn as_bows(&self) -> BytesOrWideString<'_> {
+            match self {
+                BytesOrWide::Bytes(w) => BytesOrWideString::Bytes(w),
+                BytesOrWide::Wide(w) => BytesOrWideString::Wide(w),
+            }
+        }
     }
 
     impl Debug for Backtrace {
@@ -114,7 +384,7 @@ mod capture {
             let capture = match &self.inner {
                 Inner::Unsupported => return fmt.write_str("<unsupported>"),
                 Inner::Disabled => return fmt.write_str("<disabled>"),
-                Inner::Captured(c) => c.force(),
+                Inner::Captured { capture: c, .. } => c.force(),
             };
 
             let frames = &capture.frames[capture.actual_start..];
@@ -169,10 +439,7 @@ mod capture {
         fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
             output_filename(
                 fmt,
-                match self {
-                    BytesOrWide::Bytes(w) => BytesOrWideString::Bytes(w),
-                    BytesOrWide::Wide(w) => BytesOrWideString::Wide(w),
-                },
+                self.as_bows(),
                 PrintFmt::Short,
                 env::current_dir().as_ref().ok(),
             )
@@ -198,19 +465,54 @@ mod capture {
             enabled
         }
 
+        pub(crate) const fn disabled() -> Backtrace {
+            Backtrace {
+                inner: Inner::Disabled,
+            }
+        }
+
+        /// Captures a backtrace of the current thread, if backtraces are
+        /// enabled through the `RUST_LIB_BACKTRACE` or `RUST_BACKTRACE`
+        /// environment variables.
+        ///
+        /// Like `std::backtrace::Backtrace::capture`, this is meant for
+        /// backtraces that are only sometimes wanted. Its symbols are resolved
+        /// when it is first printed.
         #[inline(never)] // want to make sure there's a frame here to remove
-        pub(crate) fn capture() -> Backtrace {
+        pub fn capture() -> Backtrace {
             if Backtrace::enabled() {
-                Backtrace::create(Backtrace::capture as usize)
+                Backtrace::create(Backtrace::capture as fn() -> Backtrace as usize, usize::MAX)
             } else {
                 let inner = Inner::Disabled;
                 Backtrace { inner }
             }
         }
 
+        /// Captures a backtrace of the current thread, regardless of the
+        /// environment variables.
+        #[inline(never)] // want to make sure there's a frame here to remove
+        pub fn force_capture() -> Backtrace {
+            Backtrace::create(
+                Backtrace::force_capture as fn() -> Backtrace as usize,
+                usize::MAX,
+            )
+        }
+
+        // Captures the innermost `limit` frames of the caller's stack,
+        // regardless of the environment variables, without walking the rest
+        // of the stack.
+        #[cfg(feature = "std")]
+        #[inline(never)] // want to make sure there's a frame here to remove
+        pub(crate) fn capture_leading(limit: usize) -> Backtrace {
+            Backtrace::create(
+                Backtrace::capture_leading as fn(usize) -> Backtrace as usize,
+                limit,
+            )
+        }
+
         // Capture a backtrace which starts just before the function addressed
-        // by `ip`
-        fn create(ip: usize) -> Backtrace {
+        // by `ip`, of at most `limit` frames from there.
+        fn create(ip: usize, limit: usize) -> Backtrace {
             let mut frames = Vec::new();
             let mut actual_start = None;
             backtrace::trace(|frame| {
@@ -218,10 +520,14 @@ mod capture {
                     frame: frame.clone(),
                     symbols: Vec::new(),
                 });
+                // Start from the caller of the function addressed by `ip`.
                 if frame.symbol_address() as usize == ip && actual_start.is_none() {
-                    actual_start = Some(frames.len() + 1);
+                    actual_start = Some(frames.len());
+                }
+                match actual_start {
+                    Some(start) => frames.len() - start < limit,
+                    None => true,
                 }
-                true
             });
 
             // If no frames came out assume that this is an unsupported platform
@@ -230,34 +536,257 @@ mod capture {
             let inner = if frames.is_empty() {
                 Inner::Unsupported
             } else {
-                Inner::Captured(LazilyResolvedCapture::new(Capture {
+                let (capture, first) = intern(LazilyResolvedCapture::new(Capture {
                     actual_start: actual_start.unwrap_or(0),
                     frames,
                     resolved: false,
-                }))
+                }));
+                Inner::Captured { capture, first }
             };
 
             Backtrace { inner }
         }
 
+        /// Identifies this backtrace among the backtraces captured by this
+        /// process.
+        ///
+        /// Backtraces captured along the same path through the code, with the
+        /// same frames, have the same id, so a log can show the full backtrace
+        /// for its [first occurrence][Backtrace::is_first_occurrence] and only
+        /// the id after that. Ids are derived from the addresses of the frames
+        /// and are not comparable between processes.
+        ///
+        /// Returns `None` if no backtrace was captured.
+        pub fn id(&self) -> Option<BacktraceId> {
+            match &self.inner {
+                Inner::Unsupported | Inner::Disabled => None,
+                Inner::Captured { capture, .. } => Some(BacktraceId { id: capture.id }),
+            }
+        }
+
+        /// Whether this is the first backtrace with its [id][Backtrace::id]
+        /// that this process captured.
+        ///
+        /// Once a process has captured backtraces with thousands of different
+        /// ids, it forgets those that are no longer held by any error, and a
+        /// backtrace with one of them counts as the first again.
+        pub fn is_first_occurrence(&self) -> bool {
+            match &self.inner {
+                Inner::Unsupported | Inner::Disabled => false,
+                Inner::Captured { first, .. } => *first,
+            }
+        }
+
+        #[cfg(std_backtrace)]
+        pub(crate) fn is_captured(&self) -> bool {
+            match self.inner {
+                Inner::Unsupported | Inner::Disabled => false,
+                Inner::Captured { .. } => true,
+            }
+        }
+
+        #[cfg(not(std_backtrace))]
         pub(crate) fn status(&self) -> BacktraceStatus {
             match self.inner {
                 Inner::Unsupported => BacktraceStatus::Unsupported,
                 Inner::Disabled => BacktraceStatus::Disabled,
-                Inner::Captured(_) => BacktraceStatus::Captured,
+                Inner::Captured { .. } => BacktraceStatus::Captured,
+            }
+        }
+
+        /// The frames from where the backtrace was captured outward, resolving
+        /// their symbols if that has not been done yet.
+        ///
+        /// A backtrace that was not captured has no frames.
+        pub fn frames(&self) -> Frames<'_> {
+            let frames = match &self.inner {
+                Inner::Unsupported | Inner::Disabled => &[],
+                Inner::Captured { capture: c, .. } => {
+                    let capture = c.force();
+                    &capture.frames[capture.actual_start..]
+                }
+            };
+            Frames {
+                inner: frames.iter(),
+            }
+        }
+
+        // The first frames of the short form of the backtrace.
+        #[cfg(feature = "std")]
+        pub(crate) fn leading(&self, limit: usize) -> String {
+            match &self.inner {
+                Inner::Unsupported | Inner::Disabled => String::new(),
+                Inner::Captured { capture: c, .. } => Short {
+                    capture: c.force(),
+                    limit: Some(limit),
+                }
+                .to_string(),
+            }
+        }
+
+        // The frames from where the backtrace was captured outward, without
+        // resolving their symbols.
+        pub(crate) fn unresolved(&self) -> Option<UnresolvedBacktrace> {
+            match &self.inner {
+                Inner::Unsupported | Inner::Disabled => None,
+                Inner::Captured { capture: c, .. } => Some(UnresolvedBacktrace::new(&c.ips)),
             }
         }
     }
 
+    impl BacktraceFrame {
+        /// The instruction pointer of this frame.
+        ///
+        /// For every frame but the innermost one, this is the return address:
+        /// the instruction following the call that is in progress.
+        pub fn ip(&self) -> *mut c_void {
+            self.frame.ip()
+        }
+
+        /// The starting address of the function that this frame is executing.
+        pub fn symbol_address(&self) -> *mut c_void {
+            self.frame.symbol_address()
+        }
+
+        /// The base address at which the module containing this frame's code,
+        /// an executable or a shared library, was loaded.
+        ///
+        /// Together with [`ip`][BacktraceFrame::ip] this locates the frame
+        /// within the module's file, for symbolicating it offline.
+        pub fn module_base_address(&self) -> Option<*mut c_void> {
+            self.frame.module_base_address()
+        }
+
+        /// The functions that this frame is executing.
+        ///
+        /// There is usually one symbol per frame. If other functions were
+        /// inlined into the frame's function at the frame's instruction pointer,
+        /// there is one symbol for each of them, the innermost inlined function
+        /// first and the function that the frame belongs to last. The list is
+        /// empty if no debug information was found for the frame.
+        pub fn symbols(&self) -> &[BacktraceSymbol] {
+            &self.symbols
+        }
+    }
+
+    impl BacktraceSymbol {
+        /// A symbol resolved from debug information by other means than this
+        /// crate, such as for [`UnresolvedBacktrace::symbolize`].
+        pub fn new(
+            name: Option<&str>,
+            filename: Option<&Path>,
+            lineno: Option<u32>,
+            colno: Option<u32>,
+        ) -> Self {
+            BacktraceSymbol {
+                name: name.map(|name| name.as_bytes().to_vec()),
+                filename: filename.map(BytesOrWide::from_path),
+                lineno,
+                colno,
+            }
+        }
+
+        /// The demangled name of the function, without the trailing hash of
+        /// Rust symbol names.
+        pub fn name(&self) -> Option<String> {
+            let name = self.name.as_ref()?;
+            Some(format!("{:#}", SymbolName::new(name)))
+        }
+
+        /// The source file that the function is defined in.
+        pub fn filename(&self) -> Option<Cow<'_, Path>> {
+            self.filename.as_ref().map(|file| path(file.as_bows()))
+        }
+
+        // The source file as printed in the short form of a backtrace,
+        // relative to the current directory if it is inside of it.
+        #[cfg(feature = "serde")]
+        pub(crate) fn printed_filename(&self) -> Option<String> {
+            self.filename.as_ref().map(|file| format!("{:?}", file))
+        }
+
+        /// The line number in the source file.
+        pub fn lineno(&self) -> Option<u32> {
+            self.lineno
+        }
+
+        /// The column number in the source file.
+        pub fn colno(&self) -> Option<u32> {
+            self.colno
+        }
+    }
+
+    impl<'a> Iterator for Frames<'a> {
+        type Item = &'a BacktraceFrame;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            self.inner.next()
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            self.inner.size_hint()
+        }
+    }
+
+    impl DoubleEndedIterator for Frames<'_> {
+        fn next_back(&mut self) -> Option<Self::Item> {
+            self.inner.next_back()
+        }
+    }
+
+    impl ExactSizeIterator for Frames<'_> {
+        fn len(&self) -> usize {
+            self.inner.len()
+        }
+    }
+
     impl Display for Backtrace {
         fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
             let capture = match &self.inner {
                 Inner::Unsupported => return fmt.write_str("unsupported backtrace"),
                 Inner::Disabled => return fmt.write_str("disabled backtrace"),
-                Inner::Captured(c) => c.force(),
+                Inner::Captured { capture: c, .. } => c.force(),
             };
 
-            let full = fmt.alternate();
+            // The short form leaves out the frames of anyhow and of the
+            // runtime, the same as in the Debug representation of an error.
+            if fmt.alternate() {
+                Display::fmt(
+                    &Printed {
+                        capture,
+                        full: true,
+                    },
+                    fmt,
+                )
+            } else if crate::frames::full() {
+                Display::fmt(
+                    &Printed {
+                        capture,
+                        full: false,
+                    },
+                    fmt,
+                )
+            } else {
+                Display::fmt(
+                    &Short {
+                        capture,
+                        limit: None,
+                    },
+                    fmt,
+                )
+            }
+        }
+    }
+
+    struct Printed<'a> {
+        capture: &'a Capture,
+        full: bool,
+    }
+
+    impl Display for Printed<'_> {
+        fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+            let capture = self.capture;
+            let full = self.full;
             let (frames, style) = if full {
                 (&capture.frames[..], PrintFmt::Full)
             } else {
@@ -284,10 +813,7 @@ mod capture {
                         f.print_raw_with_column(
                             frame.frame.ip(),
                             symbol.name.as_ref().map(|b| SymbolName::new(b)),
-                            symbol.filename.as_ref().map(|b| match b {
-                                BytesOrWide::Bytes(w) => BytesOrWideString::Bytes(w),
-                                BytesOrWide::Wide(w) => BytesOrWideString::Wide(w),
-                            }),
+                            symbol.filename.as_ref().map(BytesOrWide::as_bows),
                             symbol.lineno,
                             symbol.colno,
                         )?;
@@ -299,16 +825,101 @@ mod capture {
         }
     }
 
-    struct LazilyResolvedCapture {
+    // The short form of a backtrace, with the frames picked by the names of
+    // their functions and printed in the layout of the backtrace crate's
+    // short format, numbered from where the error was created.
+    struct Short<'a> {
+        capture: &'a Capture,
+        // Only this many of the frames or collapsed lines, without the note
+        // about omitted frames.
+        limit: Option<usize>,
+    }
+
+    impl Display for Short<'_> {
+        fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+            let frames = &self.capture.frames[self.capture.actual_start..];
+            let functions: Vec<Vec<String>> = frames
+                .iter()
+                .map(|frame| {
+                    frame
+                        .symbols
+                        .iter()
+                        .filter_map(BacktraceSymbol::name)
+                        .collect()
+                })
+                .collect();
+            let short = crate::frames::select(&functions);
+
+            let cwd = env::current_dir();
+            let parts = match self.limit {
+                Some(limit) => &short.parts[..short.parts.len().min(limit)],
+                None => &short.parts[..],
+            };
+            for part in parts {
+                let (frame, index) = match *part {
+                    Part::Frame(i) => (&frames[i], i - short.start),
+                    Part::Collapsed(krate, n) => {
+                        crate::frames::write_collapsed(fmt, krate, n)?;
+                        continue;
+                    }
+                };
+                // Like the backtrace crate, which leaves them out of the short
+                // format.
+                if frame.frame.ip().is_null() {
+                    continue;
+                }
+                write!(fmt, "{:4}: ", index)?;
+                if frame.symbols.is_empty() {
+                    writeln!(fmt, "<unknown>")?;
+                }
+                for (i, symbol) in frame.symbols.iter().enumerate() {
+                    if i > 0 {
+                        fmt.write_str("      ")?;
+                    }
+                    match &symbol.name {
+                        Some(name) => writeln!(fmt, "{:#}", SymbolName::new(name))?,
+                        None => writeln!(fmt, "<unknown>")?,
+                    }
+                    if let (Some(file), Some(line)) = (&symbol.filename, symbol.lineno) {
+                        fmt.write_str("             at ")?;
+                        output_filename(fmt, file.as_bows(), PrintFmt::Short, cwd.as_ref().ok())?;
+                        write!(fmt, ":{}", line)?;
+                        if let Some(colno) = symbol.colno {
+                            write!(fmt, ":{}", colno)?;
+                        }
+                        writeln!(fmt)?;
+                    }
+                }
+            }
+            if short.omitted && self.limit.is_none() {
+                writeln!(fmt, "{}", crate::frames::OMITTED)?;
+            }
+            Ok(())
+        }
+    }
+
+    pub(crate) struct LazilyResolvedCapture {
         sync: Once,
         capture: UnsafeCell<Capture>,
+        // The instruction pointers from actual_start on, which can be read
+        // while another thread is resolving the capture.
+        ips: Vec<usize>,
+        id: u64,
     }
 
     impl LazilyResolvedCapture {
         fn new(capture: Capture) -> Self {
+            let ips: Vec<usize> = capture.frames[capture.actual_start..]
+                .iter()
+                .map(|frame| frame.frame.ip() as usize)
+                .collect();
+            let mut hasher = DefaultHasher::new();
+            ips.hash(&mut hasher);
             LazilyResolvedCapture {
                 sync: Once::new(),
                 capture: UnsafeCell::new(capture),
+                ips,
+                id: hasher.finish(),
             }
         }
 
@@ -327,6 +938,61 @@ mod capture {
         }
     }
 
+    type Table = HashMap<u64, Weak<LazilyResolvedCapture>>;
+
+    // Backtraces with the same frames share one capture, so that its symbols
+    // are only resolved once. The table remembers the id of every backtrace
+    // captured so far, along with its capture for as long as some backtrace
+    // still refers to it.
+    const FORGET: usize = 4096;
+
+    fn intern(capture: LazilyResolvedCapture) -> (Arc<LazilyResolvedCapture>, bool) {
+        let mut table = table().lock().unwrap_or_else(PoisonError::into_inner);
+        let first = match table.get(&capture.id).map(Weak::upgrade) {
+            Some(Some(existing)) if existing.ips == capture.ips => return (existing, false),
+            // Different frames with the same hash keep their own capture.
+            Some(Some(_)) => return (Arc::new(capture), false),
+            Some(None) => false,
+            None => true,
+        };
+
+        // The ids of captures that are no longer in use are kept to tell
+        // whether a backtrace is the first with its id, until there are too
+        // many of them. Past that, they are dropped every time the table
+        // doubles in size.
+        if table.len() >= FORGET && table.len().is_power_of_two() {
+            table.retain(|_, weak| weak.upgrade().is_some());
+        }
+
+        let capture = Arc::new(capture);
+        table.insert(capture.id, Arc::downgrade(&capture));
+        (capture, first)
+    }
+
+    fn table() -> &'static Mutex<Table> {
+        static TABLE: AtomicPtr<Mutex<Table>> = AtomicPtr::new(ptr::null_mut());
+
+        let table = TABLE.load(Ordering::Acquire);
+        if !table.is_null() {
+            return unsafe { &*table };
+        }
+        let new = Box::into_raw(Box::new(Mutex::new(HashMap::new())));
+        match TABLE.compare_exchange(ptr::null_mut(), new, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => unsafe { &*new },
+            Err(table) => {
+                // Another thread got there first.
+                drop(unsafe { Box::from_raw(new) });
+                unsafe { &*table }
+            }
+        }
+    }
+
+    impl Display for BacktraceId {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{:016x}", self.id)
+        }
+    }
+
     // Safety: Access to the inner value is synchronized using a thread-safe
     // `Once`. So long as `Capture` is `Sync`, `LazilyResolvedCapture` is too
     unsafe impl Sync for LazilyResolvedCapture where Capture: Sync {}
@@ -364,7 +1030,21 @@ mod capture {
         print_fmt: PrintFmt,
         cwd: Option<&PathBuf>,
     ) -> fmt::Result {
-        let file: Cow<Path> = match bows {
+        let file = path(bows);
+        if print_fmt == PrintFmt::Short && file.is_absolute() {
+            if let Some(cwd) = cwd {
+                if let Ok(stripped) = file.strip_prefix(cwd) {
+                    if let Some(s) = stripped.to_str() {
+                        return write!(fmt, ".{}{}", path::MAIN_SEPARATOR, s);
+                    }
+                }
+            }
+        }
+        Display::fmt(&file.display(), fmt)
+    }
+
+    fn path(bows: BytesOrWideString) -> Cow<Path> {
+        match bows {
             #[cfg(unix)]
             BytesOrWideString::Bytes(bytes) => {
                 use std::os::unix::ffi::OsStrExt;
@@ -381,21 +1061,11 @@ mod capture {
             }
             #[cfg(not(windows))]
             BytesOrWideString::Wide(_wide) => Path::new("<unknown>").into(),
-        };
-        if print_fmt == PrintFmt::Short && file.is_absolute() {
-            if let Some(cwd) = cwd {
-                if let Ok(stripped) = file.strip_prefix(&cwd) {
-                    if let Some(s) = stripped.to_str() {
-                        return write!(fmt, ".{}{}", path::MAIN_SEPARATOR, s);
-                    }
-                }
-            }
         }
-        Display::fmt(&file.display(), fmt)
     }
 }
 
 fn _assert_send_sync() {
     fn _assert<T: Send + Sync>() {}
-    _assert::<Backtrace>();
+    _assert::<Captured>();
 }
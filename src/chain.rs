@@ -1,4 +1,8 @@
 use self::ChainState::*;
+#[cfg(all(feature = "std", feature = "serde"))]
+use crate::error::ErrorImpl;
+#[cfg(all(feature = "std", feature = "serde"))]
+use crate::ptr::Ref;
 use crate::StdError;
 
 #[cfg(feature = "std")]
@@ -28,8 +32,28 @@ impl<'a> Chain<'a> {
     pub fn new(head: &'a (dyn StdError + 'static)) -> Self {
         Chain {
             state: ChainState::Linked { next: Some(head) },
+            #[cfg(all(feature = "std", feature = "serde"))]
+            layers: None,
         }
     }
+
+    // A chain starting at the outermost layer of an anyhow::Error, which can
+    // report which of its errors are that error's own layers.
+    #[cfg(all(feature = "std", feature = "serde"))]
+    pub(crate) fn with_layers(
+        head: &'a (dyn StdError + 'static),
+        layers: Ref<'a, ErrorImpl>,
+    ) -> Self {
+        Chain {
+            state: ChainState::Linked { next: Some(head) },
+            layers: Some(layers),
+        }
+    }
+
+    #[cfg(all(feature = "std", feature = "serde"))]
+    pub(crate) fn layers(&self) -> Option<Ref<'a, ErrorImpl>> {
+        self.layers
+    }
 }
 
 impl<'a> Iterator for Chain<'a> {
@@ -97,6 +121,8 @@ impl Default for Chain<'_> {
             state: ChainState::Buffered {
                 rest: Vec::new().into_iter(),
             },
+            #[cfg(feature = "serde")]
+            layers: None,
         }
     }
 }
@@ -1,18 +1,85 @@
 use crate::error::ContextError;
-use crate::{Context, Error, StdError};
+#[cfg(feature = "std")]
+use crate::field::FieldList;
+#[cfg(feature = "std")]
+use crate::location::Location;
+#[cfg(feature = "std")]
+use crate::Field;
+use crate::{Context, Error, Severity, StdError};
 use core::convert::Infallible;
 use core::fmt::{self, Debug, Display, Write};
+#[cfg(feature = "std")]
+use core::sync::atomic::{AtomicUsize, Ordering};
 
-#[cfg(backtrace)]
-use std::any::{Demand, Provider};
+#[cfg(all(feature = "std", std_backtrace, not(feature = "backtrace")))]
+use crate::backtrace::Backtrace;
+#[cfg(all(feature = "std", feature = "backtrace"))]
+use crate::Backtrace;
 
-mod ext {
+#[cfg(error_generic_member_access)]
+use std::error::Request;
+
+// The number of frames to show of the backtrace captured for each layer of
+// context, or 0 to not capture one.
+#[cfg(feature = "std")]
+static LAYER_FRAMES: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(feature = "std")]
+pub(crate) fn set_layer_frames(frames: usize) {
+    LAYER_FRAMES.store(frames, Ordering::Relaxed);
+}
+
+// A backtrace captured where a layer of context was added, kept among the
+// attachments of that layer.
+#[cfg(all(feature = "std", any(std_backtrace, feature = "backtrace")))]
+pub(crate) struct LayerBacktrace {
+    pub backtrace: Backtrace,
+    pub frames: usize,
+}
+
+// Room for the frames of anyhow and of the standard library between the
+// capture and the code that added the context, which are not shown.
+#[cfg(all(feature = "std", feature = "backtrace"))]
+const LIBRARY_FRAMES: usize = 16;
+
+// With the "backtrace" feature the stack is only walked as far as the frames
+// to show; the standard library's backtrace always walks all of it.
+#[cfg(all(feature = "std", any(std_backtrace, feature = "backtrace")))]
+pub(crate) fn capture_layer_backtrace(error: &mut Error) {
+    let frames = LAYER_FRAMES.load(Ordering::Relaxed);
+    if frames > 0 {
+        error.add_attachment(LayerBacktrace {
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture_leading(frames.saturating_add(LIBRARY_FRAMES)),
+            #[cfg(not(feature = "backtrace"))]
+            backtrace: Backtrace::force_capture(),
+            frames,
+        });
+    }
+}
+
+#[cfg(not(all(feature = "std", any(std_backtrace, feature = "backtrace"))))]
+pub(crate) fn capture_layer_backtrace(error: &mut Error) {
+    let _ = error;
+}
+
+pub(crate) mod ext {
     use super::*;
 
     pub trait StdError {
         fn ext_context<C>(self, context: C) -> Error
         where
             C: Display + Send + Sync + 'static;
+
+        #[cfg(feature = "std")]
+        fn ext_context_at<C>(self, context: C, location: Option<&'static Location>) -> Error
+        where
+            C: Display + Send + Sync + 'static;
+
+        #[cfg(feature = "std")]
+        fn ext_with_field<V>(self, key: &'static str, value: V) -> Error
+        where
+            V: Display + Send + Sync + 'static;
     }
 
     #[cfg(feature = "std")]
@@ -20,6 +87,7 @@ mod ext {
     where
         E: std::error::Error + Send + Sync + 'static,
     {
+        #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
         fn ext_context<C>(self, context: C) -> Error
         where
             C: Display + Send + Sync + 'static,
@@ -27,15 +95,52 @@ mod ext {
             let backtrace = backtrace_if_absent!(&self);
             Error::from_context(context, self, backtrace)
         }
+
+        fn ext_context_at<C>(self, context: C, location: Option<&'static Location>) -> Error
+        where
+            C: Display + Send + Sync + 'static,
+        {
+            let backtrace = backtrace_if_absent!(&self);
+            Error::from_context_at(context, self, backtrace, location)
+        }
+
+        // Converted to anyhow::Error first, so that the fields of every layer
+        // are found in the same type of layer whatever the underlying error.
+        #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+        fn ext_with_field<V>(self, key: &'static str, value: V) -> Error
+        where
+            V: Display + Send + Sync + 'static,
+        {
+            let backtrace = backtrace_if_absent!(&self);
+            Error::from_std(self, backtrace).with_field(key, value)
+        }
     }
 
     impl StdError for Error {
+        #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
         fn ext_context<C>(self, context: C) -> Error
         where
             C: Display + Send + Sync + 'static,
         {
             self.context(context)
         }
+
+        #[cfg(feature = "std")]
+        fn ext_context_at<C>(self, context: C, location: Option<&'static Location>) -> Error
+        where
+            C: Display + Send + Sync + 'static,
+        {
+            self.context_at(context, location)
+        }
+
+        #[cfg(feature = "std")]
+        #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+        fn ext_with_field<V>(self, key: &'static str, value: V) -> Error
+        where
+            V: Display + Send + Sync + 'static,
+        {
+            self.with_field(key, value)
+        }
     }
 }
 
@@ -43,6 +148,7 @@ impl<T, E> Context<T, E> for Result<T, E>
 where
     E: ext::StdError + Send + Sync + 'static,
 {
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
     fn context<C>(self, context: C) -> Result<T, Error>
     where
         C: Display + Send + Sync + 'static,
@@ -55,6 +161,7 @@ where
         }
     }
 
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
     fn with_context<C, F>(self, context: F) -> Result<T, Error>
     where
         C: Display + Send + Sync + 'static,
@@ -65,6 +172,29 @@ where
             Err(error) => Err(error.ext_context(context())),
         }
     }
+
+    #[cfg(feature = "std")]
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    fn context_kv<V>(self, key: &'static str, value: V) -> Result<T, Error>
+    where
+        V: Display + Send + Sync + 'static,
+    {
+        match self {
+            Ok(ok) => Ok(ok),
+            Err(error) => Err(error.ext_with_field(key, value)),
+        }
+    }
+
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    fn context_severity<C>(self, severity: Severity, context: C) -> Result<T, Error>
+    where
+        C: Display + Send + Sync + 'static,
+    {
+        match self {
+            Ok(ok) => Ok(ok),
+            Err(error) => Err(error.ext_context(context).with_severity(severity)),
+        }
+    }
 }
 
 /// ```
@@ -88,6 +218,7 @@ where
 /// }
 /// ```
 impl<T> Context<T, Infallible> for Option<T> {
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
     fn context<C>(self, context: C) -> Result<T, Error>
     where
         C: Display + Send + Sync + 'static,
@@ -100,6 +231,7 @@ impl<T> Context<T, Infallible> for Option<T> {
         }
     }
 
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
     fn with_context<C, F>(self, context: F) -> Result<T, Error>
     where
         C: Display + Send + Sync + 'static,
@@ -110,6 +242,32 @@ impl<T> Context<T, Infallible> for Option<T> {
             None => Err(Error::from_display(context(), backtrace!())),
         }
     }
+
+    #[cfg(feature = "std")]
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    fn context_kv<V>(self, key: &'static str, value: V) -> Result<T, Error>
+    where
+        V: Display + Send + Sync + 'static,
+    {
+        match self {
+            Some(ok) => Ok(ok),
+            None => {
+                let fields = FieldList(vec![Field::new(key, value)]);
+                Err(Error::from_display(fields, backtrace!()))
+            }
+        }
+    }
+
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    fn context_severity<C>(self, severity: Severity, context: C) -> Result<T, Error>
+    where
+        C: Display + Send + Sync + 'static,
+    {
+        match self {
+            Some(ok) => Ok(ok),
+            None => Err(Error::from_display(context, backtrace!()).with_severity(severity)),
+        }
+    }
 }
 
 impl<C, E> Debug for ContextError<C, E>
@@ -143,9 +301,9 @@ where
         Some(&self.error)
     }
 
-    #[cfg(backtrace)]
-    fn provide<'a>(&'a self, demand: &mut Demand<'a>) {
-        StdError::provide(&self.error, demand);
+    #[cfg(error_generic_member_access)]
+    fn provide<'a>(&'a self, request: &mut Request<'a>) {
+        StdError::provide(&self.error, request);
     }
 }
 
@@ -157,9 +315,9 @@ where
         Some(unsafe { crate::ErrorImpl::error(self.error.inner.by_ref()) })
     }
 
-    #[cfg(backtrace)]
-    fn provide<'a>(&'a self, demand: &mut Demand<'a>) {
-        Provider::provide(&self.error, demand);
+    #[cfg(error_generic_member_access)]
+    fn provide<'a>(&'a self, request: &mut Request<'a>) {
+        Error::provide(&self.error, request);
     }
 }
 
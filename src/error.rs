@@ -1,18 +1,34 @@
+#[cfg(any(std_backtrace, feature = "backtrace"))]
 use crate::backtrace::Backtrace;
+use crate::backtrace::Captured;
 use crate::chain::Chain;
+use crate::location::Location;
 #[cfg(any(feature = "std", anyhow_no_ptr_addr_of))]
 use crate::ptr::Mut;
 use crate::ptr::{Own, Ref};
-use crate::{Error, StdError};
+use crate::report::Handler;
+#[cfg(feature = "std")]
+use crate::scope::ScopeMessage;
+#[cfg(not(anyhow_no_track_caller))]
+use crate::Locations;
+#[cfg(feature = "std")]
+use crate::SharedError;
+#[cfg(feature = "std")]
+use crate::{field::FieldList, Field, Fields};
+use crate::{Error, ReportHandler, Severity, StdError};
+use crate::{Fingerprint, FingerprintBuilder};
+#[cfg(feature = "backtrace")]
+use crate::{Frames, UnresolvedBacktrace};
 use alloc::boxed::Box;
-#[cfg(backtrace)]
-use core::any::Demand;
-use core::any::TypeId;
+use alloc::vec::Vec;
+use core::any::{Any, TypeId};
 use core::fmt::{self, Debug, Display};
-use core::mem::ManuallyDrop;
+use core::mem::{self, ManuallyDrop};
 #[cfg(not(anyhow_no_ptr_addr_of))]
 use core::ptr;
 use core::ptr::NonNull;
+#[cfg(error_generic_member_access)]
+use std::error::Request;
 
 #[cfg(feature = "std")]
 use core::ops::{Deref, DerefMut};
@@ -28,6 +44,7 @@ impl Error {
     #[cfg(feature = "std")]
     #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
     #[cold]
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
     #[must_use]
     pub fn new<E>(error: E) -> Self
     where
@@ -37,6 +54,27 @@ impl Error {
         Error::from_std(error, backtrace)
     }
 
+    /// Create a new error object from any error type, without capturing a
+    /// backtrace.
+    ///
+    /// This is like [`Error::new`] for errors that are expected to be handled
+    /// right away, in code paths hot enough that capturing a backtrace would
+    /// be noticeable. The [backtrace policy][crate::set_backtrace_policy]
+    /// does not apply. A backtrace provided by the error type itself is still
+    /// used.
+    #[cfg(feature = "std")]
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+    #[cold]
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    #[must_use]
+    pub fn new_without_backtrace<E>(error: E) -> Self
+    where
+        E: StdError + Send + Sync + 'static,
+    {
+        let backtrace = backtrace_if_absent!(&error, disabled_backtrace!());
+        Error::from_std(error, backtrace)
+    }
+
     /// Create a new error object from a printable error message.
     ///
     /// If the argument implements std::error::Error, prefer `Error::new`
@@ -75,6 +113,7 @@ impl Error {
     /// }
     /// ```
     #[cold]
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
     #[must_use]
     pub fn msg<M>(message: M) -> Self
     where
@@ -83,9 +122,96 @@ impl Error {
         Error::from_adhoc(message, backtrace!())
     }
 
+    /// Create a new error object from a printable error message, without
+    /// capturing a backtrace.
+    ///
+    /// This is like [`Error::msg`] for errors that are expected to be handled
+    /// right away, in code paths hot enough that capturing a backtrace would
+    /// be noticeable. The [backtrace policy][crate::set_backtrace_policy]
+    /// does not apply.
+    ///
+    /// ```
+    /// use anyhow::{Error, Result};
+    ///
+    /// fn parse_digit(c: char) -> Result<u32> {
+    ///     c.to_digit(10)
+    ///         .ok_or_else(|| Error::msg_without_backtrace("not a digit"))
+    /// }
+    ///
+    /// let digits: Vec<u32> = "a1b2c3".chars().filter_map(|c| parse_digit(c).ok()).collect();
+    /// assert_eq!(digits, [1, 2, 3]);
+    /// ```
+    #[cold]
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    #[must_use]
+    pub fn msg_without_backtrace<M>(message: M) -> Self
+    where
+        M: Display + Debug + Send + Sync + 'static,
+    {
+        Error::from_adhoc(message, disabled_backtrace!())
+    }
+
+    /// Create a single error object out of many independent errors.
+    ///
+    /// The resulting error displays as "N errors occurred". Its Debug
+    /// representation lists every child error along with the child's own
+    /// chain of causes, and the children are available through
+    /// [`children()`][Error::children].
+    ///
+    /// Downcasting an aggregate searches each of its children in order, so
+    /// `is::<E>()` answers whether any branch of the tree holds an `E`.
+    ///
+    /// ```
+    /// use anyhow::{anyhow, Error};
+    ///
+    /// let results = vec![Ok(1), Err(anyhow!("bad row 2")), Err(anyhow!("bad row 3"))];
+    /// let errors = results.into_iter().filter_map(Result::err);
+    /// let error = Error::aggregate(errors);
+    ///
+    /// assert_eq!(error.to_string(), "2 errors occurred");
+    /// assert_eq!(error.children().len(), 2);
+    /// ```
+    #[cfg(feature = "std")]
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+    #[cold]
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    #[must_use]
+    pub fn aggregate<I>(errors: I) -> Self
+    where
+        I: IntoIterator<Item = Error>,
+    {
+        let error = AggregateError {
+            errors: errors.into_iter().collect(),
+        };
+
+        let vtable = &ErrorVTable {
+            object_drop: object_drop::<AggregateError>,
+            object_ref: object_ref::<AggregateError>,
+            #[cfg(anyhow_no_ptr_addr_of)]
+            object_mut: object_mut::<AggregateError>,
+            object_boxed: object_boxed::<AggregateError>,
+            object_downcast: aggregate_downcast,
+            object_downcast_ref: aggregate_downcast_ref,
+            #[cfg(anyhow_no_ptr_addr_of)]
+            object_downcast_mut: aggregate_downcast_mut,
+            object_drop_rest: aggregate_drop_rest,
+            object_next: no_next,
+            object_owned: object_owned::<AggregateError>,
+            #[cfg(all(
+                not(error_generic_member_access),
+                any(std_backtrace, feature = "backtrace")
+            ))]
+            object_backtrace: no_backtrace,
+        };
+
+        // Safety: passing vtable that operates on the right type.
+        unsafe { Error::construct(error, vtable, backtrace!()) }
+    }
+
     #[cfg(feature = "std")]
     #[cold]
-    pub(crate) fn from_std<E>(error: E, backtrace: Option<Backtrace>) -> Self
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    pub(crate) fn from_std<E>(error: E, backtrace: Option<Captured>) -> Self
     where
         E: StdError + Send + Sync + 'static,
     {
@@ -96,10 +222,16 @@ impl Error {
             object_mut: object_mut::<E>,
             object_boxed: object_boxed::<E>,
             object_downcast: object_downcast::<E>,
+            object_downcast_ref: object_downcast::<E>,
             #[cfg(anyhow_no_ptr_addr_of)]
             object_downcast_mut: object_downcast_mut::<E>,
             object_drop_rest: object_drop_front::<E>,
-            #[cfg(all(not(backtrace), feature = "backtrace"))]
+            object_next: no_next,
+            object_owned: object_owned::<E>,
+            #[cfg(all(
+                not(error_generic_member_access),
+                any(std_backtrace, feature = "backtrace")
+            ))]
             object_backtrace: no_backtrace,
         };
 
@@ -108,7 +240,8 @@ impl Error {
     }
 
     #[cold]
-    pub(crate) fn from_adhoc<M>(message: M, backtrace: Option<Backtrace>) -> Self
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    pub(crate) fn from_adhoc<M>(message: M, backtrace: Option<Captured>) -> Self
     where
         M: Display + Debug + Send + Sync + 'static,
     {
@@ -121,10 +254,16 @@ impl Error {
             object_mut: object_mut::<MessageError<M>>,
             object_boxed: object_boxed::<MessageError<M>>,
             object_downcast: object_downcast::<M>,
+            object_downcast_ref: object_downcast::<M>,
             #[cfg(anyhow_no_ptr_addr_of)]
             object_downcast_mut: object_downcast_mut::<M>,
             object_drop_rest: object_drop_front::<M>,
-            #[cfg(all(not(backtrace), feature = "backtrace"))]
+            object_next: no_next,
+            object_owned: object_owned::<M>,
+            #[cfg(all(
+                not(error_generic_member_access),
+                any(std_backtrace, feature = "backtrace")
+            ))]
             object_backtrace: no_backtrace,
         };
 
@@ -134,7 +273,8 @@ impl Error {
     }
 
     #[cold]
-    pub(crate) fn from_display<M>(message: M, backtrace: Option<Backtrace>) -> Self
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    pub(crate) fn from_display<M>(message: M, backtrace: Option<Captured>) -> Self
     where
         M: Display + Send + Sync + 'static,
     {
@@ -147,10 +287,16 @@ impl Error {
             object_mut: object_mut::<DisplayError<M>>,
             object_boxed: object_boxed::<DisplayError<M>>,
             object_downcast: object_downcast::<M>,
+            object_downcast_ref: object_downcast::<M>,
             #[cfg(anyhow_no_ptr_addr_of)]
             object_downcast_mut: object_downcast_mut::<M>,
             object_drop_rest: object_drop_front::<M>,
-            #[cfg(all(not(backtrace), feature = "backtrace"))]
+            object_next: no_next,
+            object_owned: object_owned::<M>,
+            #[cfg(all(
+                not(error_generic_member_access),
+                any(std_backtrace, feature = "backtrace")
+            ))]
             object_backtrace: no_backtrace,
         };
 
@@ -161,7 +307,25 @@ impl Error {
 
     #[cfg(feature = "std")]
     #[cold]
-    pub(crate) fn from_context<C, E>(context: C, error: E, backtrace: Option<Backtrace>) -> Self
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    pub(crate) fn from_context<C, E>(context: C, error: E, backtrace: Option<Captured>) -> Self
+    where
+        C: Display + Send + Sync + 'static,
+        E: StdError + Send + Sync + 'static,
+    {
+        Error::from_context_at(context, error, backtrace, location!())
+    }
+
+    // Like from_context, located where the layer was added rather than at the
+    // caller, for context added by an adapter such as a future.
+    #[cfg(feature = "std")]
+    #[cold]
+    pub(crate) fn from_context_at<C, E>(
+        context: C,
+        error: E,
+        backtrace: Option<Captured>,
+        location: Option<&'static Location>,
+    ) -> Self
     where
         C: Display + Send + Sync + 'static,
         E: StdError + Send + Sync + 'static,
@@ -175,22 +339,34 @@ impl Error {
             object_mut: object_mut::<ContextError<C, E>>,
             object_boxed: object_boxed::<ContextError<C, E>>,
             object_downcast: context_downcast::<C, E>,
+            object_downcast_ref: context_downcast::<C, E>,
             #[cfg(anyhow_no_ptr_addr_of)]
             object_downcast_mut: context_downcast_mut::<C, E>,
             object_drop_rest: context_drop_rest::<C, E>,
-            #[cfg(all(not(backtrace), feature = "backtrace"))]
+            object_next: no_next,
+            object_owned: context_owned::<C, E>,
+            #[cfg(all(
+                not(error_generic_member_access),
+                any(std_backtrace, feature = "backtrace")
+            ))]
             object_backtrace: no_backtrace,
         };
 
-        // Safety: passing vtable that operates on the right type.
-        unsafe { Error::construct(error, vtable, backtrace) }
+        // Safety: passing vtable that operates on the right type. The scopes
+        // go outside of the layer of context, as for any other new error.
+        let mut error = unsafe { Error::construct_layer(error, vtable, backtrace, location) };
+        crate::context::capture_layer_backtrace(&mut error);
+        let error = crate::scope::apply(error);
+        crate::hook::invoke(&error);
+        error
     }
 
     #[cfg(feature = "std")]
     #[cold]
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
     pub(crate) fn from_boxed(
         error: Box<dyn StdError + Send + Sync>,
-        backtrace: Option<Backtrace>,
+        backtrace: Option<Captured>,
     ) -> Self {
         use crate::wrapper::BoxedError;
         let error = BoxedError(error);
@@ -201,10 +377,16 @@ impl Error {
             object_mut: object_mut::<BoxedError>,
             object_boxed: object_boxed::<BoxedError>,
             object_downcast: object_downcast::<Box<dyn StdError + Send + Sync>>,
+            object_downcast_ref: object_downcast::<Box<dyn StdError + Send + Sync>>,
             #[cfg(anyhow_no_ptr_addr_of)]
             object_downcast_mut: object_downcast_mut::<Box<dyn StdError + Send + Sync>>,
             object_drop_rest: object_drop_front::<Box<dyn StdError + Send + Sync>>,
-            #[cfg(all(not(backtrace), feature = "backtrace"))]
+            object_next: no_next,
+            object_owned: object_owned::<Box<dyn StdError + Send + Sync>>,
+            #[cfg(all(
+                not(error_generic_member_access),
+                any(std_backtrace, feature = "backtrace")
+            ))]
             object_backtrace: no_backtrace,
         };
 
@@ -213,16 +395,93 @@ impl Error {
         unsafe { Error::construct(error, vtable, backtrace) }
     }
 
+    #[cfg(feature = "std")]
+    #[cold]
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    pub(crate) fn from_shared(error: SharedError) -> Self {
+        use crate::wrapper::SharedWrapper;
+        let error = SharedWrapper(error);
+        let vtable = &ErrorVTable {
+            object_drop: object_drop::<SharedWrapper>,
+            object_ref: object_ref::<SharedWrapper>,
+            #[cfg(anyhow_no_ptr_addr_of)]
+            object_mut: object_mut::<SharedWrapper>,
+            object_boxed: object_boxed::<SharedWrapper>,
+            object_downcast: object_downcast::<SharedError>,
+            object_downcast_ref: shared_downcast_ref,
+            #[cfg(anyhow_no_ptr_addr_of)]
+            object_downcast_mut: object_downcast_mut::<SharedError>,
+            object_drop_rest: object_drop_front::<SharedError>,
+            object_next: shared_next,
+            object_owned: shared_owned,
+            #[cfg(all(
+                not(error_generic_member_access),
+                any(std_backtrace, feature = "backtrace")
+            ))]
+            object_backtrace: shared_backtrace,
+        };
+
+        // The shared error already has its own backtrace.
+        let backtrace = None;
+
+        // Safety: SharedWrapper is repr(transparent) so it is okay for the
+        // vtable to allow casting to SharedError. The hook is not invoked, as
+        // this is not a new error.
+        let mut error = unsafe { Error::construct_unhooked(error, vtable, backtrace) };
+
+        // The wrapper is transparent; the shared error's own layers already
+        // report where it originated.
+        unsafe { error.inner.by_mut().deref_mut().location = None };
+        error
+    }
+
     // Takes backtrace as argument rather than capturing it here so that the
     // user sees one fewer layer of wrapping noise in the backtrace.
     //
     // Unsafe because the given vtable must have sensible behavior on the error
     // value of type E.
     #[cold]
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
     unsafe fn construct<E>(
         error: E,
         vtable: &'static ErrorVTable,
-        backtrace: Option<Backtrace>,
+        backtrace: Option<Captured>,
+    ) -> Self
+    where
+        E: StdError + Send + Sync + 'static,
+    {
+        let error = Error::construct_layer(error, vtable, backtrace, location!());
+        #[cfg(feature = "std")]
+        let error = crate::scope::apply(error);
+        #[cfg(feature = "std")]
+        crate::hook::invoke(&error);
+        error
+    }
+
+    // Like construct, but for a layer of context on an existing error, which
+    // has already been wrapped in the scopes of this thread, located at the
+    // given location. The caller invokes the hook if the layer calls for it.
+    #[cold]
+    unsafe fn construct_layer<E>(
+        error: E,
+        vtable: &'static ErrorVTable,
+        backtrace: Option<Captured>,
+        location: Option<&'static Location>,
+    ) -> Self
+    where
+        E: StdError + Send + Sync + 'static,
+    {
+        let mut error = Error::construct_unhooked(error, vtable, backtrace);
+        error.inner.by_mut().deref_mut().location = location;
+        error
+    }
+
+    #[cold]
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    unsafe fn construct_unhooked<E>(
+        error: E,
+        vtable: &'static ErrorVTable,
+        backtrace: Option<Captured>,
     ) -> Self
     where
         E: StdError + Send + Sync + 'static,
@@ -230,6 +489,8 @@ impl Error {
         let inner: Box<ErrorImpl<E>> = Box::new(ErrorImpl {
             vtable,
             backtrace,
+            location: location!(),
+            attachments: Vec::new(),
             _object: error,
         });
         // Erase the concrete type of E from the compile-time type system. This
@@ -297,8 +558,43 @@ impl Error {
     /// }
     /// ```
     #[cold]
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
     #[must_use]
     pub fn context<C>(self, context: C) -> Self
+    where
+        C: Display + Send + Sync + 'static,
+    {
+        self.context_at(context, location!())
+    }
+
+    // Like context, located where the layer was added rather than at the
+    // caller, for context added by an adapter such as a future. The layer goes
+    // beneath those of the scopes entered on this thread.
+    #[cold]
+    pub(crate) fn context_at<C>(self, context: C, location: Option<&'static Location>) -> Self
+    where
+        C: Display + Send + Sync + 'static,
+    {
+        let add = |error: Error| {
+            let mut error = error.layer_at(context, location);
+            crate::context::capture_layer_backtrace(&mut error);
+            error
+        };
+
+        #[cfg(feature = "std")]
+        let error = crate::scope::beneath(self, add);
+        #[cfg(not(feature = "std"))]
+        let error = add(self);
+
+        #[cfg(feature = "std")]
+        crate::hook::invoke(&error);
+        error
+    }
+
+    // Wraps the error in a layer of context as is, without regard for scopes
+    // and without invoking the hook, such as for the layer of a scope itself.
+    #[cold]
+    pub(crate) fn layer_at<C>(self, context: C, location: Option<&'static Location>) -> Self
     where
         C: Display + Send + Sync + 'static,
     {
@@ -314,10 +610,16 @@ impl Error {
             object_mut: object_mut::<ContextError<C, Error>>,
             object_boxed: object_boxed::<ContextError<C, Error>>,
             object_downcast: context_chain_downcast::<C>,
+            object_downcast_ref: context_chain_downcast_ref::<C>,
             #[cfg(anyhow_no_ptr_addr_of)]
             object_downcast_mut: context_chain_downcast_mut::<C>,
             object_drop_rest: context_chain_drop_rest::<C>,
-            #[cfg(all(not(backtrace), feature = "backtrace"))]
+            object_next: context_chain_next::<C>,
+            object_owned: object_owned::<C>,
+            #[cfg(all(
+                not(error_generic_member_access),
+                any(std_backtrace, feature = "backtrace")
+            ))]
             object_backtrace: context_backtrace::<C>,
         };
 
@@ -325,7 +627,251 @@ impl Error {
         let backtrace = None;
 
         // Safety: passing vtable that operates on the right type.
-        unsafe { Error::construct(error, vtable, backtrace) }
+        unsafe { Error::construct_layer(error, vtable, backtrace, location) }
+    }
+
+    // The scope that the outermost layer of this error is the layer of, if
+    // any.
+    #[cfg(feature = "std")]
+    fn scope(&self) -> Option<&ScopeMessage> {
+        let outermost = unsafe { ErrorImpl::error(self.inner.by_ref()) };
+        let layer = outermost.downcast_ref::<ContextError<ScopeMessage, Error>>()?;
+        Some(&layer.context)
+    }
+
+    // Takes the layer of a scope off of the error, to be put back around it
+    // once more context has been added beneath it.
+    #[cfg(feature = "std")]
+    pub(crate) fn split_scope(self) -> Result<(ScopeLayer, Error), Error> {
+        if self.scope().is_none() {
+            return Err(self);
+        }
+        let outer = ManuallyDrop::new(self);
+        // Safety: the outermost layer is a ContextError<ScopeMessage, Error>,
+        // with the same layout as ContextError<ScopeMessage,
+        // ManuallyDrop<Error>>. The layer no longer owns the error inside it.
+        let mut layer = unsafe {
+            outer
+                .inner
+                .cast::<ErrorImpl<ContextError<ScopeMessage, ManuallyDrop<Error>>>>()
+                .boxed()
+        };
+        let error = unsafe { ManuallyDrop::take(&mut layer._object.error) };
+        Ok((ScopeLayer { layer }, error))
+    }
+
+    /// Wrap the error value with a key-value pair of context.
+    ///
+    /// Unlike a context message formatted from the same values, the field
+    /// keeps its key and its typed value, so that structured logging can pick
+    /// them up through [`fields()`][Error::fields] rather than parse them out
+    /// of a string. In the Display representation, the fields of a layer are
+    /// shown as `key=value` pairs separated by spaces.
+    ///
+    /// Calling `with_field` again right away adds to the same layer instead of
+    /// creating another one, ahead of the fields already in it so that fields
+    /// read outermost first as layers do.
+    ///
+    /// ```
+    /// use anyhow::anyhow;
+    ///
+    /// let error = anyhow!("payment declined")
+    ///     .with_field("user", 3)
+    ///     .with_field("order", 7)
+    ///     .context("failed to place order");
+    ///
+    /// assert_eq!(
+    ///     format!("{:#}", error),
+    ///     "failed to place order: order=7 user=3: payment declined",
+    /// );
+    /// ```
+    #[cfg(feature = "std")]
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+    #[cold]
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    #[must_use]
+    pub fn with_field<V>(self, key: &'static str, value: V) -> Self
+    where
+        V: Display + Send + Sync + 'static,
+    {
+        let field = Field::new(key, value);
+        let location = location!();
+        let mut added = false;
+        let error = crate::scope::beneath(self, |mut error| {
+            let outermost = unsafe { ErrorImpl::error_mut(error.inner.by_mut()) };
+            if let Some(fields) = crate::field::outermost_mut(outermost) {
+                fields.0.insert(0, field);
+                return error;
+            }
+            added = true;
+            let mut error = error.layer_at(FieldList(vec![field]), location);
+            crate::context::capture_layer_backtrace(&mut error);
+            error
+        });
+        if added {
+            crate::hook::invoke(&error);
+        }
+        error
+    }
+
+    /// Set how serious this error is.
+    ///
+    /// The severity is attached to the outermost layer of the error. It can be
+    /// set on any layer, and [`severity()`][Error::severity] reports the
+    /// highest of them.
+    ///
+    /// ```
+    /// use anyhow::{anyhow, Severity};
+    ///
+    /// let error = anyhow!("card declined")
+    ///     .with_severity(Severity::Info)
+    ///     .context("failed to charge card")
+    ///     .with_severity(Severity::Warning);
+    ///
+    /// assert_eq!(error.severity(), Some(Severity::Warning));
+    /// assert!(format!("{:?}", error).starts_with("[warning] failed to charge card"));
+    /// ```
+    #[cold]
+    #[must_use]
+    pub fn with_severity(self, severity: Severity) -> Self {
+        self.attach(severity)
+    }
+
+    /// How serious this error is.
+    ///
+    /// This is the highest [severity][Severity] set on any layer of the error
+    /// with [`with_severity`][Error::with_severity], including the errors held
+    /// by an [aggregate][Error::aggregate], or `None` if none was set.
+    pub fn severity(&self) -> Option<Severity> {
+        unsafe { ErrorImpl::severity(self.inner.by_ref()) }
+    }
+
+    /// Attach a typed value to this error.
+    ///
+    /// Attachments carry structured data alongside an error as it propagates,
+    /// such as a request id or an HTTP status code, without defining a
+    /// dedicated error type for it. They are not part of the error's Display
+    /// or Debug representation and do not affect downcasting; retrieve them
+    /// with [`request_ref`][Error::request_ref].
+    ///
+    /// Attachments are kept when further context is added to the error.
+    ///
+    /// ```
+    /// use anyhow::{anyhow, Result};
+    ///
+    /// #[derive(Debug, PartialEq)]
+    /// struct RequestId(u64);
+    ///
+    /// fn handle() -> Result<()> {
+    ///     Err(anyhow!("connection reset").attach(RequestId(7)))
+    /// }
+    ///
+    /// let error = handle().unwrap_err().context("failed to handle request");
+    /// assert_eq!(error.request_ref::<RequestId>(), Some(&RequestId(7)));
+    /// ```
+    #[cold]
+    #[must_use]
+    pub fn attach<T>(mut self, value: T) -> Self
+    where
+        T: Send + Sync + 'static,
+    {
+        self.add_attachment(value);
+        self
+    }
+
+    /// Attach a typed value to this error in place.
+    ///
+    /// This is the same as [`attach`][Error::attach] for when only a mutable
+    /// reference to the error is available, such as in a hook installed with
+    /// [`set_hook`][crate::set_hook].
+    #[cold]
+    pub fn add_attachment<T>(&mut self, value: T)
+    where
+        T: Send + Sync + 'static,
+    {
+        unsafe {
+            let inner = self.inner.by_mut().deref_mut();
+            inner.attachments.push(Box::new(value));
+        }
+    }
+
+    /// Look up a value of type `T` attached to this error.
+    ///
+    /// Every layer of context is searched, starting from the outermost one,
+    /// so values attached before a call to `context` are still found. If
+    /// multiple values of the same type were attached, the one that was
+    /// attached last is returned.
+    pub fn request_ref<T>(&self) -> Option<&T>
+    where
+        T: 'static,
+    {
+        unsafe { ErrorImpl::request_ref(self.inner.by_ref()) }
+    }
+
+    /// Render this error with the given handler instead of the global one.
+    ///
+    /// The handler determines how the error is printed with `{:?}`, including
+    /// when it is returned from `fn main`. It stays in effect when further
+    /// context is added to the error; if several layers of the error have a
+    /// handler, the outermost one is used. See [`ReportHandler`] for an
+    /// example.
+    #[cold]
+    #[must_use]
+    pub fn with_handler<H>(self, handler: H) -> Self
+    where
+        H: ReportHandler,
+    {
+        self.attach(Handler(Box::new(handler)))
+    }
+
+    /// Create an error from the payload of a panic.
+    ///
+    /// This is for the `Err` returned by `std::panic::catch_unwind` or by
+    /// `std::thread::JoinHandle::join`. The resulting error holds a
+    /// [`PanicError`][crate::PanicError] with the message of the panic. If the
+    /// panic was caught by [`anyhow::catch_unwind`][crate::catch_unwind], it
+    /// also carries the location of the panic and the backtrace captured when
+    /// it was raised.
+    ///
+    /// A panic whose payload is itself an `anyhow::Error`, as raised by
+    /// `std::panic::panic_any(error)`, gives back that error unchanged.
+    ///
+    /// ```
+    /// use anyhow::{Error, PanicError};
+    /// use std::thread;
+    ///
+    /// let payload = thread::spawn(|| panic!("oh no!")).join().unwrap_err();
+    /// let error = Error::from_panic(payload);
+    /// assert!(error.is::<PanicError>());
+    /// assert_eq!(error.to_string(), "oh no!");
+    /// ```
+    #[cfg(feature = "std")]
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+    #[cold]
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    #[must_use]
+    pub fn from_panic(payload: Box<dyn Any + Send>) -> Self {
+        let payload = match payload.downcast::<Error>() {
+            Ok(error) => return *error,
+            Err(payload) => payload,
+        };
+        let (error, mut backtrace) = crate::panic::error(&*payload);
+        if backtrace.is_none() {
+            backtrace = backtrace!();
+        }
+        Error::from_std(error, backtrace)
+    }
+
+    /// Convert this error into a cloneable [`SharedError`].
+    ///
+    /// This is useful for handing the same failure to multiple consumers,
+    /// such as every waiter on a cached computation that failed. The shared
+    /// error can later be converted back into an `anyhow::Error` using
+    /// `From`/`Into`.
+    #[cfg(feature = "std")]
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+    pub fn into_shared(self) -> SharedError {
+        SharedError::from(self)
     }
 
     /// Get the backtrace for this Error.
@@ -345,25 +891,211 @@ impl Error {
     ///
     /// # Stability
     ///
-    /// Standard library backtraces are only available on the nightly channel.
-    /// Tracking issue: [rust-lang/rust#53487][tracking].
+    /// Standard library backtraces are stable since Rust 1.65. On those
+    /// compilers this function returns a `std::backtrace::Backtrace`.
     ///
-    /// On stable compilers, this function is only available if the crate's
+    /// On older compilers, this function is only available if the crate's
     /// "backtrace" feature is enabled, and will use the `backtrace` crate as
-    /// the underlying backtrace implementation.
+    /// the underlying backtrace implementation. The feature also gives access
+    /// to the individual frames of the backtrace through `Error::frames`, on
+    /// any compiler. To walk the stack only once per error, on Rust 1.65 and
+    /// newer the feature captures the backtrace of a new error with the
+    /// `backtrace` crate only: the Debug representation of the error shows it
+    /// and `Error::frames_backtrace` returns it, while this function returns a
+    /// disabled backtrace unless the error was given one with
+    /// [`with_backtrace`][Error::with_backtrace].
     ///
     /// ```toml
     /// [dependencies]
     /// anyhow = { version = "1.0", features = ["backtrace"] }
     /// ```
-    ///
-    /// [tracking]: https://github.com/rust-lang/rust/issues/53487
-    #[cfg(any(backtrace, feature = "backtrace"))]
-    #[cfg_attr(doc_cfg, doc(cfg(any(nightly, feature = "backtrace"))))]
+    #[cfg(any(std_backtrace, feature = "backtrace"))]
+    #[cfg_attr(doc_cfg, doc(cfg(any(feature = "std", feature = "backtrace"))))]
     pub fn backtrace(&self) -> &impl_backtrace!() {
         unsafe { ErrorImpl::backtrace(self.inner.by_ref()) }
     }
 
+    /// Replace the backtrace of this error with one captured elsewhere.
+    ///
+    /// This is for errors that are created away from where things went wrong,
+    /// such as on a thread that collects the failures of worker threads. The
+    /// worker captures the backtrace and sends it along with its error, so
+    /// that the `anyhow::Error` created from them reports where the worker
+    /// failed.
+    ///
+    /// The backtrace is of the same type as returned by
+    /// [`backtrace()`][Error::backtrace], a `std::backtrace::Backtrace` on Rust
+    /// 1.65 and newer. Its frames are not available through `Error::frames`,
+    /// which only knows those of the backtraces that anyhow captures itself.
+    ///
+    /// ```
+    /// use anyhow::anyhow;
+    /// use std::backtrace::Backtrace;
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    ///
+    /// let (sender, receiver) = mpsc::channel();
+    /// thread::spawn(move || {
+    ///     let backtrace = Backtrace::capture();
+    ///     sender.send(("disk full", backtrace)).unwrap();
+    /// });
+    ///
+    /// let (message, backtrace) = receiver.recv().unwrap();
+    /// let error = anyhow!(message).with_backtrace(backtrace);
+    /// # let _ = error;
+    /// ```
+    #[cfg(any(std_backtrace, feature = "backtrace"))]
+    #[cfg_attr(doc_cfg, doc(cfg(any(feature = "std", feature = "backtrace"))))]
+    #[must_use]
+    pub fn with_backtrace(mut self, backtrace: Backtrace) -> Self {
+        unsafe {
+            let inner = self.inner.by_mut().deref_mut();
+            inner.backtrace = Some(Captured::new(backtrace));
+        }
+        self
+    }
+
+    /// Create a new error object from any error type and a backtrace captured
+    /// elsewhere.
+    ///
+    /// This is [`Error::new`] followed by
+    /// [`with_backtrace`][Error::with_backtrace], without capturing a
+    /// backtrace in between.
+    #[cfg(all(feature = "std", any(std_backtrace, feature = "backtrace")))]
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+    #[cold]
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    #[must_use]
+    pub fn from_parts<E>(error: E, backtrace: Backtrace) -> Self
+    where
+        E: StdError + Send + Sync + 'static,
+    {
+        Error::from_std(error, Some(Captured::new(backtrace)))
+    }
+
+    /// Iterate over the frames of the backtrace for this Error.
+    ///
+    /// This gives the same frames that the [backtrace][Error::backtrace]
+    /// prints, innermost first, with their instruction pointer, module base
+    /// address, and the name, source file, line and column of each function,
+    /// including inlined ones. If no backtrace was captured, there are no
+    /// frames.
+    ///
+    /// The standard library's backtrace does not give access to its frames,
+    /// so with the "backtrace" feature they are captured using the
+    /// `backtrace` crate instead.
+    ///
+    /// ```
+    /// use anyhow::anyhow;
+    ///
+    /// let error = anyhow!("oh no!");
+    /// for frame in error.frames() {
+    ///     for symbol in frame.symbols() {
+    ///         let name = symbol.name().unwrap_or_default();
+    ///         match (symbol.filename(), symbol.lineno()) {
+    ///             (Some(file), Some(line)) => {
+    ///                 println!("{:?} {} at {}:{}", frame.ip(), name, file.display(), line);
+    ///             }
+    ///             _ => println!("{:?} {}", frame.ip(), name),
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    #[cfg(feature = "backtrace")]
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "backtrace")))]
+    pub fn frames(&self) -> Frames<'_> {
+        self.frames_backtrace().frames()
+    }
+
+    /// Get the backtrace whose [frames][Error::frames] this Error gives
+    /// access to, which also identifies the backtraces with the same frames.
+    ///
+    /// On Rust 1.65 and newer, this is captured in place of the standard
+    /// library's backtrace returned by [`backtrace()`][Error::backtrace], and
+    /// on older compilers it is that same backtrace. It is disabled for an
+    /// error that was given its backtrace with
+    /// [`with_backtrace`][Error::with_backtrace].
+    ///
+    /// ```
+    /// use anyhow::anyhow;
+    ///
+    /// let error = anyhow!("oh no!");
+    /// if let Some(id) = error.frames_backtrace().id() {
+    ///     if error.frames_backtrace().is_first_occurrence() {
+    ///         eprintln!("backtrace {}:\n{}", id, error.frames_backtrace());
+    ///     } else {
+    ///         eprintln!("backtrace {}", id);
+    ///     }
+    /// }
+    /// ```
+    #[cfg(feature = "backtrace")]
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "backtrace")))]
+    pub fn frames_backtrace(&self) -> &crate::Backtrace {
+        static DISABLED: crate::Backtrace = crate::Backtrace::disabled();
+        unsafe { ErrorImpl::frames(self.inner.by_ref()) }.unwrap_or(&DISABLED)
+    }
+
+    /// Get the backtrace for this Error without resolving its frames, to be
+    /// symbolicated elsewhere.
+    ///
+    /// Resolving a backtrace needs the debug information of the program,
+    /// which stripped release binaries do not have. The
+    /// [`UnresolvedBacktrace`] identifies each frame by the module it belongs
+    /// to and its address within the module, and prints in a compact form that
+    /// is cheap to send along with the error, to be symbolicated later with
+    /// the debug information kept on the build server. Getting it does not
+    /// resolve the backtrace of this error.
+    ///
+    /// Returns `None` if no backtrace was captured.
+    ///
+    /// ```
+    /// use anyhow::anyhow;
+    ///
+    /// let error = anyhow!("oh no!");
+    /// if let Some(backtrace) = error.unresolved_backtrace() {
+    ///     // Small enough to include in a log record or a crash report.
+    ///     let text = backtrace.to_string();
+    ///     assert_eq!(backtrace, text.parse().unwrap());
+    /// }
+    /// ```
+    #[cfg(feature = "backtrace")]
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "backtrace")))]
+    pub fn unresolved_backtrace(&self) -> Option<UnresolvedBacktrace> {
+        self.frames_backtrace().unresolved()
+    }
+
+    /// The source location at which this error originated.
+    ///
+    /// This is the place where the innermost `anyhow::Error` was created, for
+    /// example by `anyhow!`, `bail!`, `Error::new`, or the `?` operator
+    /// converting some other error type. Unlike a backtrace, the location is
+    /// always recorded because it is essentially free to capture.
+    ///
+    /// Use [`locations()`][Error::locations] to also see where each layer of
+    /// context was attached.
+    #[cfg(not(anyhow_no_track_caller))]
+    pub fn location(&self) -> &'static core::panic::Location<'static> {
+        self.locations().last().expect("location capture failed")
+    }
+
+    /// An iterator of the source locations of each layer of this error,
+    /// from the outermost context to the place where the error originated.
+    #[cfg(not(anyhow_no_track_caller))]
+    pub fn locations(&self) -> Locations<'_> {
+        Locations {
+            layers: ErrorImpl::layers(self.inner.by_ref()),
+        }
+    }
+
+    /// A stable hash of this error for grouping occurrences of the same
+    /// failure, computed with the default configuration.
+    ///
+    /// See [`Fingerprint`] for what goes into it, and
+    /// [`Fingerprint::builder`] to configure it.
+    pub fn fingerprint(&self) -> Fingerprint {
+        FingerprintBuilder::new().fingerprint(self)
+    }
+
     /// An iterator of the chain of source errors contained by this Error.
     ///
     /// This iterator will visit every error in the cause chain of this error
@@ -388,7 +1120,7 @@ impl Error {
     #[cfg(feature = "std")]
     #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
     #[cold]
-    pub fn chain(&self) -> Chain {
+    pub fn chain(&self) -> Chain<'_> {
         unsafe { ErrorImpl::chain(self.inner.by_ref()) }
     }
 
@@ -403,6 +1135,51 @@ impl Error {
         self.chain().last().unwrap()
     }
 
+    /// The fields of this error and of its causes, outermost first.
+    ///
+    /// These are the key-value pairs added with
+    /// [`with_field`][Error::with_field] and
+    /// [`Context::context_kv`][crate::Context::context_kv] to any layer of the
+    /// chain.
+    ///
+    /// ```
+    /// use anyhow::{Context, Result};
+    /// use std::fs;
+    ///
+    /// fn load(user: u32) -> Result<String> {
+    ///     let path = format!("/users/{}.toml", user);
+    ///     fs::read_to_string(&path).context_kv("path", path)
+    /// }
+    ///
+    /// let error = load(3).context_kv("user", 3).unwrap_err();
+    /// let fields: Vec<String> = error.fields().map(|field| field.to_string()).collect();
+    /// assert_eq!(fields, ["user=3", "path=/users/3.toml"]);
+    /// assert_eq!(error.fields().next().unwrap().downcast_ref::<i32>(), Some(&3));
+    /// ```
+    #[cfg(feature = "std")]
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+    pub fn fields(&self) -> Fields<'_> {
+        Fields::new(self.chain())
+    }
+
+    /// The errors held by an aggregate created with
+    /// [`Error::aggregate`][Error::aggregate].
+    ///
+    /// Context attached on top of an aggregate is looked through, so this
+    /// returns the children of the outermost aggregate in the chain. For an
+    /// error that is not an aggregate, the slice is empty.
+    #[cfg(feature = "std")]
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+    pub fn children(&self) -> &[Error] {
+        let target = TypeId::of::<AggregateError>();
+        unsafe {
+            match (vtable(self.inner.ptr).object_downcast_ref)(self.inner.by_ref(), target) {
+                Some(addr) => &addr.cast::<AggregateError>().deref().errors,
+                None => &[],
+            }
+        }
+    }
+
     /// Returns true if `E` is the type held by this error object.
     ///
     /// For errors with context, this method returns true if `E` matches the
@@ -497,7 +1274,7 @@ impl Error {
         unsafe {
             // Use vtable to find NonNull<()> which points to a value of type E
             // somewhere inside the data structure.
-            let addr = (vtable(self.inner.ptr).object_downcast)(self.inner.by_ref(), target)?;
+            let addr = (vtable(self.inner.ptr).object_downcast_ref)(self.inner.by_ref(), target)?;
             Some(addr.cast::<E>().deref())
         }
     }
@@ -524,15 +1301,20 @@ impl Error {
     }
 }
 
-#[cfg(backtrace)]
-impl std::any::Provider for Error {
+#[cfg(error_generic_member_access)]
+impl Error {
+    pub(crate) fn provide<'a>(&'a self, request: &mut Request<'a>) {
+        unsafe { ErrorImpl::provide(self.inner.by_ref(), request) }
+    }
+
     // Called by thiserror when you have `#[source] anyhow::Error`. This provide
     // implementation includes the anyhow::Error's Backtrace if any, unlike
     // deref'ing to dyn Error where the provide implementation would include
     // only the original error's Backtrace from before it got wrapped into an
     // anyhow::Error.
-    fn provide<'a>(&'a self, demand: &mut Demand<'a>) {
-        unsafe { ErrorImpl::provide(self.inner.by_ref(), demand) }
+    #[doc(hidden)]
+    pub fn thiserror_provide<'a>(&'a self, request: &mut Request<'a>) {
+        Self::provide(self, request);
     }
 }
 
@@ -543,6 +1325,7 @@ where
     E: StdError + Send + Sync + 'static,
 {
     #[cold]
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
     fn from(error: E) -> Self {
         let backtrace = backtrace_if_absent!(&error);
         Error::from_std(error, backtrace)
@@ -575,7 +1358,7 @@ impl Display for Error {
 
 impl Debug for Error {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        unsafe { ErrorImpl::debug(self.inner.by_ref(), formatter) }
+        crate::report::debug(self, formatter)
     }
 }
 
@@ -594,11 +1377,25 @@ struct ErrorVTable {
     #[cfg(all(feature = "std", anyhow_no_ptr_addr_of))]
     object_mut: unsafe fn(Mut<ErrorImpl>) -> &mut (dyn StdError + Send + Sync + 'static),
     object_boxed: unsafe fn(Own<ErrorImpl>) -> Box<dyn StdError + Send + Sync + 'static>,
+    // Finds a value that is exclusively owned by this error, suitable for
+    // downcasting by value or by mutable reference.
     object_downcast: unsafe fn(Ref<ErrorImpl>, TypeId) -> Option<Ref<()>>,
+    // Like object_downcast but may also find values behind shared ownership,
+    // suitable only for downcasting by shared reference.
+    object_downcast_ref: unsafe fn(Ref<ErrorImpl>, TypeId) -> Option<Ref<()>>,
     #[cfg(anyhow_no_ptr_addr_of)]
     object_downcast_mut: unsafe fn(Mut<ErrorImpl>, TypeId) -> Option<Mut<()>>,
     object_drop_rest: unsafe fn(Own<ErrorImpl>, TypeId),
-    #[cfg(all(not(backtrace), feature = "backtrace"))]
+    // The anyhow::Error wrapped by this one, if any, such as the cause of an
+    // Error::context layer.
+    object_next: unsafe fn(Ref<ErrorImpl>) -> Option<Ref<ErrorImpl>>,
+    // Lists the errors owned by this layer in chain order: the layer's own
+    // error, followed by the error wrapped by a Context::context layer.
+    object_owned: for<'a> unsafe fn(Ref<'a, ErrorImpl>, &mut Vec<OwnedError<'a>>),
+    #[cfg(all(
+        not(error_generic_member_access),
+        any(std_backtrace, feature = "backtrace")
+    ))]
     object_backtrace: unsafe fn(Ref<ErrorImpl>) -> Option<&Backtrace>,
 }
 
@@ -700,12 +1497,51 @@ where
     }
 }
 
-#[cfg(all(not(backtrace), feature = "backtrace"))]
-fn no_backtrace(e: Ref<ErrorImpl>) -> Option<&Backtrace> {
+#[cfg(all(
+    not(error_generic_member_access),
+    any(std_backtrace, feature = "backtrace")
+))]
+fn no_backtrace(e: Ref<'_, ErrorImpl>) -> Option<&Backtrace> {
     let _ = e;
     None
 }
 
+fn no_next(e: Ref<ErrorImpl>) -> Option<Ref<ErrorImpl>> {
+    let _ = e;
+    None
+}
+
+// Safety: requires layout of *e to match ErrorImpl<E>.
+unsafe fn object_owned<'a, T>(e: Ref<'a, ErrorImpl>, owned: &mut Vec<OwnedError<'a>>) {
+    owned.push(OwnedError::new(
+        ErrorImpl::error(e),
+        e,
+        true,
+        core::any::type_name::<T>(),
+    ));
+}
+
+// Safety: requires layout of *e to match ErrorImpl<ContextError<C, E>>.
+#[cfg(feature = "std")]
+unsafe fn context_owned<'a, C, E>(e: Ref<'a, ErrorImpl>, owned: &mut Vec<OwnedError<'a>>)
+where
+    E: StdError + 'static,
+{
+    let unerased = e.cast::<ErrorImpl<ContextError<C, E>>>().deref();
+    owned.push(OwnedError::new(
+        ErrorImpl::error(e),
+        e,
+        true,
+        core::any::type_name::<C>(),
+    ));
+    owned.push(OwnedError::new(
+        &unerased._object.error,
+        e,
+        false,
+        core::any::type_name::<E>(),
+    ));
+}
+
 // Safety: requires layout of *e to match ErrorImpl<ContextError<C, E>>.
 #[cfg(feature = "std")]
 unsafe fn context_downcast<C, E>(e: Ref<ErrorImpl>, target: TypeId) -> Option<Ref<()>>
@@ -779,6 +1615,21 @@ where
     }
 }
 
+// Safety: requires layout of *e to match ErrorImpl<ContextError<C, Error>>.
+unsafe fn context_chain_downcast_ref<C>(e: Ref<ErrorImpl>, target: TypeId) -> Option<Ref<()>>
+where
+    C: 'static,
+{
+    let unerased = e.cast::<ErrorImpl<ContextError<C, Error>>>().deref();
+    if TypeId::of::<C>() == target {
+        Some(Ref::new(&unerased._object.context).cast::<()>())
+    } else {
+        // Recurse down the context chain per the inner error's vtable.
+        let source = &unerased._object.error;
+        (vtable(source.inner.ptr).object_downcast_ref)(source.inner.by_ref(), target)
+    }
+}
+
 // Safety: requires layout of *e to match ErrorImpl<ContextError<C, Error>>.
 #[cfg(anyhow_no_ptr_addr_of)]
 unsafe fn context_chain_downcast_mut<C>(e: Mut<ErrorImpl>, target: TypeId) -> Option<Mut<()>>
@@ -822,9 +1673,12 @@ where
 }
 
 // Safety: requires layout of *e to match ErrorImpl<ContextError<C, Error>>.
-#[cfg(all(not(backtrace), feature = "backtrace"))]
+#[cfg(all(
+    not(error_generic_member_access),
+    any(std_backtrace, feature = "backtrace")
+))]
 #[allow(clippy::unnecessary_wraps)]
-unsafe fn context_backtrace<C>(e: Ref<ErrorImpl>) -> Option<&Backtrace>
+unsafe fn context_backtrace<C>(e: Ref<'_, ErrorImpl>) -> Option<&Backtrace>
 where
     C: 'static,
 {
@@ -833,18 +1687,146 @@ where
     Some(backtrace)
 }
 
+// Safety: requires layout of *e to match ErrorImpl<ContextError<C, Error>>.
+#[allow(clippy::unnecessary_wraps)]
+unsafe fn context_chain_next<C>(e: Ref<ErrorImpl>) -> Option<Ref<ErrorImpl>>
+where
+    C: 'static,
+{
+    let unerased = e.cast::<ErrorImpl<ContextError<C, Error>>>().deref();
+    Some(unerased._object.error.inner.by_ref())
+}
+
+// Safety: requires layout of *e to match ErrorImpl<SharedWrapper>.
+#[cfg(feature = "std")]
+unsafe fn shared_downcast_ref(e: Ref<ErrorImpl>, target: TypeId) -> Option<Ref<()>> {
+    let unerased = e.cast::<ErrorImpl<crate::wrapper::SharedWrapper>>().deref();
+    let shared = &unerased._object.0;
+    if TypeId::of::<SharedError>() == target {
+        Some(Ref::new(shared).cast::<()>())
+    } else {
+        // Look inside the shared error per its own vtable. Values found this
+        // way are owned by the Arc, never by this error.
+        let source: &Error = shared;
+        (vtable(source.inner.ptr).object_downcast_ref)(source.inner.by_ref(), target)
+    }
+}
+
+// Safety: requires layout of *e to match ErrorImpl<SharedWrapper>.
+#[cfg(all(
+    not(error_generic_member_access),
+    any(std_backtrace, feature = "backtrace")
+))]
+#[allow(clippy::unnecessary_wraps)]
+unsafe fn shared_backtrace(e: Ref<'_, ErrorImpl>) -> Option<&Backtrace> {
+    let unerased = e.cast::<ErrorImpl<crate::wrapper::SharedWrapper>>().deref();
+    let source: &Error = &unerased._object.0;
+    let backtrace = ErrorImpl::backtrace(source.inner.by_ref());
+    Some(backtrace)
+}
+
+// Safety: requires layout of *e to match ErrorImpl<SharedWrapper>.
+#[cfg(feature = "std")]
+#[allow(clippy::unnecessary_wraps)]
+unsafe fn shared_next(e: Ref<ErrorImpl>) -> Option<Ref<ErrorImpl>> {
+    let unerased = e.cast::<ErrorImpl<crate::wrapper::SharedWrapper>>().deref();
+    let source: &Error = &unerased._object.0;
+    Some(source.inner.by_ref())
+}
+
+// Safety: requires layout of *e to match ErrorImpl<SharedWrapper>.
+#[cfg(feature = "std")]
+unsafe fn shared_owned<'a>(e: Ref<'a, ErrorImpl>, owned: &mut Vec<OwnedError<'a>>) {
+    // The wrapper stands in for the outermost layer of the shared error, whose
+    // own errors are listed after this one by way of object_next.
+    let unerased = e.cast::<ErrorImpl<crate::wrapper::SharedWrapper>>().deref();
+    let source = unerased._object.0.inner.inner.by_ref();
+    let mut inner = Vec::new();
+    (vtable(source.ptr).object_owned)(source, &mut inner);
+    owned.push(OwnedError::new(
+        ErrorImpl::error(e),
+        e,
+        true,
+        inner[0].type_name,
+    ));
+}
+
+// Safety: requires layout of *e to match ErrorImpl<AggregateError>.
+#[cfg(feature = "std")]
+unsafe fn aggregate_downcast(e: Ref<ErrorImpl>, target: TypeId) -> Option<Ref<()>> {
+    // Search each branch in order; the first match wins.
+    let unerased = e.cast::<ErrorImpl<AggregateError>>().deref();
+    unerased
+        ._object
+        .errors
+        .iter()
+        .find_map(|error| (vtable(error.inner.ptr).object_downcast)(error.inner.by_ref(), target))
+}
+
+// Safety: requires layout of *e to match ErrorImpl<AggregateError>.
+#[cfg(feature = "std")]
+unsafe fn aggregate_downcast_ref(e: Ref<ErrorImpl>, target: TypeId) -> Option<Ref<()>> {
+    let unerased = e.cast::<ErrorImpl<AggregateError>>().deref();
+    if TypeId::of::<AggregateError>() == target {
+        Some(Ref::new(&unerased._object).cast::<()>())
+    } else {
+        unerased._object.errors.iter().find_map(|error| {
+            (vtable(error.inner.ptr).object_downcast_ref)(error.inner.by_ref(), target)
+        })
+    }
+}
+
+// Safety: requires layout of *e to match ErrorImpl<AggregateError>.
+#[cfg(all(feature = "std", anyhow_no_ptr_addr_of))]
+unsafe fn aggregate_downcast_mut(e: Mut<ErrorImpl>, target: TypeId) -> Option<Mut<()>> {
+    let unerased = e.cast::<ErrorImpl<AggregateError>>().deref_mut();
+    unerased._object.errors.iter_mut().find_map(|error| {
+        (vtable(error.inner.ptr).object_downcast_mut)(error.inner.by_mut(), target)
+    })
+}
+
+// Safety: requires layout of *e to match ErrorImpl<AggregateError>.
+#[cfg(feature = "std")]
+unsafe fn aggregate_drop_rest(e: Own<ErrorImpl>, target: TypeId) {
+    // Called after downcasting by value to a value held by one of the
+    // children and doing a ptr::read to take ownership of that value. The
+    // search is repeated to find which child that was; the other children
+    // are dropped normally.
+    let unerased = e.cast::<ErrorImpl<AggregateError>>().boxed();
+    let mut found = false;
+    for error in unerased._object.errors {
+        let vtable = vtable(error.inner.ptr);
+        if !found && (vtable.object_downcast)(error.inner.by_ref(), target).is_some() {
+            found = true;
+            let error = ManuallyDrop::new(error);
+            (vtable.object_drop_rest)(error.inner, target);
+        } else {
+            drop(error);
+        }
+    }
+}
+
 // NOTE: If working with `ErrorImpl<()>`, references should be avoided in favor
 // of raw pointers and `NonNull`.
 // repr C to ensure that E remains in the final position.
 #[repr(C)]
 pub(crate) struct ErrorImpl<E = ()> {
     vtable: &'static ErrorVTable,
-    backtrace: Option<Backtrace>,
+    backtrace: Option<Captured>,
+    location: Option<&'static Location>,
+    attachments: Vec<Box<dyn Any + Send + Sync>>,
     // NOTE: Don't use directly. Use only through vtable. Erased type may have
     // different alignment.
     _object: E,
 }
 
+// Every error carries these fields ahead of its object. Besides the backtrace,
+// the vtable, the location and the attachments take five words; a new field
+// grows every anyhow::Error and needs to be worth it.
+const _: [(); 0] = [(); mem::size_of::<ErrorImpl>()
+    - mem::size_of::<Option<Captured>>()
+    - 5 * mem::size_of::<usize>()];
+
 // Reads the vtable out of `p`. This is the same as `p.as_ref().vtable`, but
 // avoids converting `p` into a reference.
 unsafe fn vtable(p: NonNull<ErrorImpl>) -> &'static ErrorVTable {
@@ -852,6 +1834,87 @@ unsafe fn vtable(p: NonNull<ErrorImpl>) -> &'static ErrorVTable {
     *(p.as_ptr() as *const &'static ErrorVTable)
 }
 
+// The errors in the chain of an anyhow::Error that are owned by its layers,
+// as opposed to sources reached through some other error's source().
+//
+// Errors are told apart by address. Each owned error is matched at most once,
+// in chain order, so that a source stored at the start of the error holding
+// it is not mistaken for that error.
+#[derive(Default)]
+pub(crate) struct Owned<'a> {
+    errors: Vec<OwnedError<'a>>,
+}
+
+pub(crate) struct OwnedError<'a> {
+    addr: *const (),
+    pub layer: Ref<'a, ErrorImpl>,
+    // Whether this is the layer's own error rather than one it wraps.
+    pub head: bool,
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))]
+    pub type_name: &'static str,
+}
+
+impl<'a> OwnedError<'a> {
+    fn new(
+        error: &(dyn StdError + 'static),
+        layer: Ref<'a, ErrorImpl>,
+        head: bool,
+        type_name: &'static str,
+    ) -> Self {
+        OwnedError {
+            addr: error as *const dyn StdError as *const (),
+            layer,
+            head,
+            type_name,
+        }
+    }
+}
+
+impl<'a> Owned<'a> {
+    // To be called with each error of the chain in order.
+    pub(crate) fn take(&mut self, error: &(dyn StdError + 'static)) -> Option<OwnedError<'a>> {
+        let addr = error as *const dyn StdError as *const ();
+        let index = self.errors.iter().position(|owned| owned.addr == addr)?;
+        Some(self.errors.remove(index))
+    }
+}
+
+pub(crate) struct Layers<'a> {
+    next: Option<Ref<'a, ErrorImpl>>,
+}
+
+impl<'a> Iterator for Layers<'a> {
+    type Item = Ref<'a, ErrorImpl>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let layer = self.next?;
+        self.next = unsafe { (vtable(layer.ptr).object_next)(layer) };
+        Some(layer)
+    }
+}
+
+// The layer of a scope, split off of the error it wrapped. Dropping it does not
+// drop that error.
+#[cfg(feature = "std")]
+pub(crate) struct ScopeLayer {
+    layer: Box<ErrorImpl<ContextError<ScopeMessage, ManuallyDrop<Error>>>>,
+}
+
+#[cfg(feature = "std")]
+impl ScopeLayer {
+    pub(crate) fn scope(&self) -> &ScopeMessage {
+        &self.layer._object.context
+    }
+
+    pub(crate) fn wrap(mut self, error: Error) -> Error {
+        self.layer._object.error = ManuallyDrop::new(error);
+        // Safety: the layer is once again a ContextError<ScopeMessage, Error>,
+        // which its vtable operates on.
+        let inner = Own::new(self.layer).cast::<ErrorImpl>();
+        Error { inner }
+    }
+}
+
 // repr C to ensure that ContextError<C, E> has the same layout as
 // ContextError<ManuallyDrop<C>, E> and ContextError<C, ManuallyDrop<E>>.
 #[repr(C)]
@@ -860,8 +1923,13 @@ pub(crate) struct ContextError<C, E> {
     pub error: E,
 }
 
+#[cfg(feature = "std")]
+pub(crate) struct AggregateError {
+    pub errors: Vec<Error>,
+}
+
 impl<E> ErrorImpl<E> {
-    fn erase(&self) -> Ref<ErrorImpl> {
+    fn erase(&self) -> Ref<'_, ErrorImpl> {
         // Erase the concrete type of E but preserve the vtable in self.vtable
         // for manipulating the resulting thin pointer. This is analogous to an
         // unsize coercion.
@@ -870,14 +1938,16 @@ impl<E> ErrorImpl<E> {
 }
 
 impl ErrorImpl {
-    pub(crate) unsafe fn error(this: Ref<Self>) -> &(dyn StdError + Send + Sync + 'static) {
+    pub(crate) unsafe fn error(this: Ref<'_, Self>) -> &(dyn StdError + Send + Sync + 'static) {
         // Use vtable to attach E's native StdError vtable for the right
         // original type E.
         (vtable(this.ptr).object_ref)(this).deref()
     }
 
     #[cfg(feature = "std")]
-    pub(crate) unsafe fn error_mut(this: Mut<Self>) -> &mut (dyn StdError + Send + Sync + 'static) {
+    pub(crate) unsafe fn error_mut(
+        this: Mut<'_, Self>,
+    ) -> &mut (dyn StdError + Send + Sync + 'static) {
         // Use vtable to attach E's native StdError vtable for the right
         // original type E.
 
@@ -890,34 +1960,126 @@ impl ErrorImpl {
         return (vtable(this.ptr).object_mut)(this);
     }
 
-    #[cfg(any(backtrace, feature = "backtrace"))]
-    pub(crate) unsafe fn backtrace(this: Ref<Self>) -> &Backtrace {
+    #[cfg(any(std_backtrace, feature = "backtrace"))]
+    pub(crate) unsafe fn backtrace(this: Ref<'_, Self>) -> &Backtrace {
         // This unwrap can only panic if the underlying error's backtrace method
         // is nondeterministic, which would only happen in maliciously
         // constructed code.
         this.deref()
             .backtrace
             .as_ref()
+            .map(|captured| &captured.backtrace)
             .or_else(|| {
-                #[cfg(backtrace)]
-                return Self::error(this).request_ref::<Backtrace>();
-                #[cfg(not(backtrace))]
+                #[cfg(error_generic_member_access)]
+                return std::error::request_ref::<Backtrace>(Self::error(this));
+                #[cfg(not(error_generic_member_access))]
                 return (vtable(this.ptr).object_backtrace)(this);
             })
             .expect("backtrace capture failed")
     }
 
-    #[cfg(backtrace)]
-    unsafe fn provide<'a>(this: Ref<'a, Self>, demand: &mut Demand<'a>) {
-        if let Some(backtrace) = &this.deref().backtrace {
-            demand.provide_ref(backtrace);
+    // The backtrace with the frames that the error's own backtrace was
+    // captured with, which is that backtrace itself without std_backtrace.
+    #[cfg(feature = "backtrace")]
+    pub(crate) unsafe fn frames(this: Ref<'_, Self>) -> Option<&crate::Backtrace> {
+        #[cfg(not(std_backtrace))]
+        return Some(Self::backtrace(this));
+
+        // A layer of context around another anyhow::Error has the frames of
+        // that error.
+        #[cfg(std_backtrace)]
+        return match &this.deref().backtrace {
+            Some(captured) => Some(captured.frames()),
+            None => Self::frames((vtable(this.ptr).object_next)(this)?),
+        };
+    }
+
+    #[cfg(error_generic_member_access)]
+    unsafe fn provide<'a>(this: Ref<'a, Self>, request: &mut Request<'a>) {
+        if let Some(captured) = &this.deref().backtrace {
+            request.provide_ref(&captured.backtrace);
         }
-        Self::error(this).provide(demand);
+        Self::error(this).provide(request);
+    }
+
+    pub(crate) unsafe fn location(this: Ref<Self>) -> Option<&'static Location> {
+        this.deref().location
+    }
+
+    pub(crate) unsafe fn owned(this: Ref<Self>) -> Owned {
+        let mut owned = Vec::new();
+        for layer in Self::layers(this) {
+            (vtable(layer.ptr).object_owned)(layer, &mut owned);
+        }
+        Owned { errors: owned }
+    }
+
+    pub(crate) unsafe fn request_ref<T>(this: Ref<'_, Self>) -> Option<&T>
+    where
+        T: 'static,
+    {
+        Self::layers(this).find_map(|layer| {
+            // Later attachments take precedence over earlier ones.
+            let attachments = &layer.deref().attachments;
+            attachments
+                .iter()
+                .rev()
+                .find_map(|attachment| attachment.downcast_ref::<T>())
+        })
+    }
+
+    // The highest severity attached to any layer of this error, or to any of
+    // the errors of an aggregate within it.
+    pub(crate) unsafe fn severity(this: Ref<Self>) -> Option<Severity> {
+        let layers = Self::layers(this)
+            .flat_map(|layer| layer.deref().attachments.iter())
+            .filter_map(|attachment| attachment.downcast_ref::<Severity>())
+            .copied()
+            .max();
+
+        #[cfg(feature = "std")]
+        let layers = {
+            let target = TypeId::of::<AggregateError>();
+            let children = match (vtable(this.ptr).object_downcast_ref)(this, target) {
+                Some(addr) => &addr.cast::<AggregateError>().deref().errors[..],
+                None => &[],
+            };
+            let children = children.iter().filter_map(Error::severity).max();
+            layers.max(children)
+        };
+
+        layers
+    }
+
+    // An attachment of this layer itself, not looking at the layers below.
+    #[cfg(all(feature = "std", any(std_backtrace, feature = "backtrace")))]
+    pub(crate) unsafe fn own_attachment<T>(this: Ref<'_, Self>) -> Option<&T>
+    where
+        T: 'static,
+    {
+        let attachments = &this.deref().attachments;
+        attachments
+            .iter()
+            .rev()
+            .find_map(|attachment| attachment.downcast_ref::<T>())
+    }
+
+    // Iterates over this error followed by every anyhow::Error that it wraps,
+    // outermost first.
+    pub(crate) fn layers(this: Ref<Self>) -> Layers {
+        Layers { next: Some(this) }
     }
 
     #[cold]
     pub(crate) unsafe fn chain(this: Ref<Self>) -> Chain {
-        Chain::new(Self::error(this))
+        #[cfg(all(feature = "std", feature = "serde"))]
+        {
+            Chain::with_layers(Self::error(this), this)
+        }
+        #[cfg(not(all(feature = "std", feature = "serde")))]
+        {
+            Chain::new(Self::error(this))
+        }
     }
 }
 
@@ -929,9 +2091,9 @@ where
         unsafe { ErrorImpl::error(self.erase()).source() }
     }
 
-    #[cfg(backtrace)]
-    fn provide<'a>(&'a self, demand: &mut Demand<'a>) {
-        unsafe { ErrorImpl::provide(self.erase(), demand) }
+    #[cfg(error_generic_member_access)]
+    fn provide<'a>(&'a self, request: &mut Request<'a>) {
+        unsafe { ErrorImpl::provide(self.erase(), request) }
     }
 }
 
@@ -0,0 +1,132 @@
+use crate::error::ContextError;
+use crate::wrapper::DisplayError;
+use crate::{Chain, Error, Field, Fields, StdError};
+use core::any::Any;
+use core::fmt::{self, Debug, Display};
+
+// The value of a field, which can be displayed or downcast to its own type.
+pub(crate) trait Value: Send + Sync {
+    fn as_display(&self) -> &(dyn Display + Send + Sync + 'static);
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<V> Value for V
+where
+    V: Display + Send + Sync + 'static,
+{
+    fn as_display(&self) -> &(dyn Display + Send + Sync + 'static) {
+        self
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// The fields of one layer of context, displayed as `key=value` pairs separated
+// by spaces.
+pub(crate) struct FieldList(pub Vec<Field>);
+
+impl Field {
+    pub(crate) fn new<V>(key: &'static str, value: V) -> Self
+    where
+        V: Display + Send + Sync + 'static,
+    {
+        Field {
+            key,
+            value: Box::new(value),
+        }
+    }
+
+    /// The key of this field.
+    pub fn key(&self) -> &'static str {
+        self.key
+    }
+
+    /// The value of this field, for display.
+    pub fn value(&self) -> &(dyn Display + Send + Sync + 'static) {
+        self.value.as_display()
+    }
+
+    /// The value of this field, if it is of type `V`.
+    pub fn downcast_ref<V>(&self) -> Option<&V>
+    where
+        V: Display + Send + Sync + 'static,
+    {
+        self.value.as_any().downcast_ref::<V>()
+    }
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.key, self.value())
+    }
+}
+
+impl Debug for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Field")
+            .field("key", &self.key)
+            .field("value", &format_args!("{}", self.value()))
+            .finish()
+    }
+}
+
+impl Display for FieldList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, field) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            Display::fmt(field, f)?;
+        }
+        Ok(())
+    }
+}
+
+// The fields of one error of the chain: a layer added by `with_field`, or the
+// error created by `context_kv` on an Option.
+fn fields_of<'a>(cause: &'a (dyn StdError + 'static)) -> &'a [Field] {
+    if let Some(layer) = cause.downcast_ref::<ContextError<FieldList, Error>>() {
+        &layer.context.0
+    } else if let Some(message) = cause.downcast_ref::<DisplayError<FieldList>>() {
+        &(message.0).0
+    } else {
+        &[]
+    }
+}
+
+// The fields of the outermost layer of an error, to which more can be added.
+pub(crate) fn outermost_mut<'a>(
+    error: &'a mut (dyn StdError + Send + Sync + 'static),
+) -> Option<&'a mut FieldList> {
+    if error.is::<ContextError<FieldList, Error>>() {
+        let layer = error.downcast_mut::<ContextError<FieldList, Error>>()?;
+        Some(&mut layer.context)
+    } else {
+        let message = error.downcast_mut::<DisplayError<FieldList>>()?;
+        Some(&mut message.0)
+    }
+}
+
+impl<'a> Fields<'a> {
+    pub(crate) fn new(chain: Chain<'a>) -> Self {
+        Fields {
+            chain,
+            layer: [].iter(),
+        }
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = &'a Field;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(field) = self.layer.next() {
+                return Some(field);
+            }
+            self.layer = fields_of(self.chain.next()?).iter();
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use crate::error::ErrorImpl;
+use crate::{Error, Fingerprint, FingerprintBuilder, StdError};
+use alloc::string::String;
+use core::fmt::{self, Display};
+
+#[cfg(all(feature = "std", feature = "serde"))]
+use crate::RemoteError;
+
+impl Fingerprint {
+    /// Configure how fingerprints are computed.
+    pub fn builder() -> FingerprintBuilder {
+        FingerprintBuilder::new()
+    }
+
+    /// The fingerprint as a number.
+    pub fn as_u64(&self) -> u64 {
+        self.hash
+    }
+}
+
+impl Display for Fingerprint {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{:016x}", self.hash)
+    }
+}
+
+impl FingerprintBuilder {
+    /// The default configuration: type names and messages, no frames.
+    pub fn new() -> Self {
+        FingerprintBuilder {
+            type_names: true,
+            messages: true,
+            frames: 0,
+        }
+    }
+
+    /// Whether to include the Rust type name of each error in the chain.
+    ///
+    /// Type names come from [`core::any::type_name`], whose output may
+    /// change from one version of the compiler to the next.
+    pub fn type_names(&mut self, enable: bool) -> &mut Self {
+        self.type_names = enable;
+        self
+    }
+
+    /// Whether to include the message of each error in the chain.
+    pub fn messages(&mut self, enable: bool) -> &mut Self {
+        self.messages = enable;
+        self
+    }
+
+    /// Include the function names of up to `n` frames of the backtrace,
+    /// starting from the one that created the error and skipping frames
+    /// inside of anyhow.
+    ///
+    /// Frames only contribute if a backtrace was captured. Line numbers are
+    /// left out, so that unrelated edits to a file do not change the
+    /// fingerprint.
+    pub fn frames(&mut self, n: usize) -> &mut Self {
+        self.frames = n;
+        self
+    }
+
+    /// Compute the fingerprint of an error.
+    pub fn fingerprint(&self, error: &Error) -> Fingerprint {
+        let this = error.inner.by_ref();
+        let mut hasher = Hasher::new();
+        let mut owned = unsafe { ErrorImpl::owned(this) };
+        for error in unsafe { ErrorImpl::chain(this) } {
+            let owner = owned.take(error);
+            if self.type_names {
+                let type_name =
+                    remote_type_name(error).or_else(|| owner.map(|owner| owner.type_name));
+                hasher.write(type_name.unwrap_or("").as_bytes());
+                hasher.separator();
+            }
+            if self.messages {
+                write_normalized(&mut hasher, format_args!("{}", error));
+                hasher.separator();
+            }
+        }
+
+        #[cfg(feature = "std")]
+        {
+            if self.frames > 0 {
+                if let Some(functions) = unsafe { crate::frames::function_names(this) } {
+                    let functions = functions
+                        .iter()
+                        .map(|function| strip_hash(function))
+                        .filter(|function| !crate::frames::is_internal(function))
+                        .take(self.frames);
+                    for function in functions {
+                        write_normalized(&mut hasher, format_args!("{}", function));
+                        hasher.separator();
+                    }
+                }
+            }
+        }
+
+        Fingerprint {
+            hash: hasher.finish(),
+        }
+    }
+}
+
+impl Default for FingerprintBuilder {
+    fn default() -> Self {
+        FingerprintBuilder::new()
+    }
+}
+
+// Errors rebuilt from a remote one report the type of the original error.
+fn remote_type_name<'a>(error: &'a (dyn StdError + 'static)) -> Option<&'a str> {
+    #[cfg(all(feature = "std", feature = "serde"))]
+    {
+        if let Some(remote) = error.downcast_ref::<RemoteError>() {
+            return Some(remote.type_name().unwrap_or(""));
+        }
+    }
+
+    let _ = error;
+    None
+}
+
+// Symbols demangled by the legacy scheme end in a hash of the crate, which
+// changes from one build to the next.
+#[cfg(feature = "std")]
+fn strip_hash(function: &str) -> &str {
+    match function.rfind("::h") {
+        Some(i)
+            if function.len() - i == 19
+                && function[i + 3..].bytes().all(|b| b.is_ascii_hexdigit()) =>
+        {
+            &function[..i]
+        }
+        _ => function,
+    }
+}
+
+// 64-bit FNV-1a, which unlike the hashers in std is specified to produce the
+// same output everywhere.
+struct Hasher {
+    state: u64,
+}
+
+impl Hasher {
+    fn new() -> Self {
+        Hasher {
+            state: 0xcbf2_9ce4_8422_2325,
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.state ^= u64::from(byte);
+            self.state = self.state.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    // Keeps adjacent fields from running together, so that "ab" + "c" and
+    // "a" + "bc" hash differently.
+    fn separator(&mut self) {
+        self.write(&[0xff]);
+    }
+
+    fn finish(&self) -> u64 {
+        self.state
+    }
+}
+
+// Hashes the text with each number in it replaced by a single '#'. A number
+// is a token, i.e. a run of letters, digits and underscores, made up of
+// decimal digits only, or of hexadecimal ones after a 0x prefix as in
+// addresses. Digits within a word, as in "2fa" or "ipv6", are kept.
+fn write_normalized(hasher: &mut Hasher, text: fmt::Arguments) {
+    let mut normalize = Normalize {
+        hasher,
+        token: String::new(),
+    };
+    let _ = fmt::write(&mut normalize, text);
+    normalize.end_token();
+}
+
+struct Normalize<'a> {
+    hasher: &'a mut Hasher,
+    // The token at the end of the text so far, which may continue in the next
+    // piece of the text.
+    token: String,
+}
+
+impl Normalize<'_> {
+    fn end_token(&mut self) {
+        if is_number(&self.token) {
+            self.hasher.write(b"#");
+        } else {
+            self.hasher.write(self.token.as_bytes());
+        }
+        self.token.clear();
+    }
+}
+
+impl fmt::Write for Normalize<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for ch in s.chars() {
+            if ch.is_alphanumeric() || ch == '_' {
+                self.token.push(ch);
+            } else {
+                self.end_token();
+                self.hasher.write(ch.encode_utf8(&mut [0; 4]).as_bytes());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_number(token: &str) -> bool {
+    let (digits, radix) = if token.starts_with("0x") || token.starts_with("0X") {
+        (&token[2..], 16)
+    } else {
+        (token, 10)
+    };
+    !digits.is_empty() && digits.chars().all(|ch| ch.is_digit(radix))
+}
@@ -1,8 +1,17 @@
 use crate::chain::Chain;
-use crate::error::ErrorImpl;
+use crate::error::{ErrorImpl, Owned};
+use crate::location::Location;
 use crate::ptr::Ref;
+use crate::StdError;
 use core::fmt::{self, Debug, Write};
 
+#[cfg(feature = "std")]
+use crate::error::AggregateError;
+#[cfg(all(feature = "std", feature = "serde"))]
+use crate::RemoteError;
+#[cfg(feature = "std")]
+use crate::{Error, PanicError};
+
 impl ErrorImpl {
     pub(crate) unsafe fn display(this: Ref<Self>, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", Self::error(this))?;
@@ -23,48 +32,200 @@ impl ErrorImpl {
             return Debug::fmt(error, f);
         }
 
+        if let Some(severity) = Self::severity(this) {
+            write!(f, "[{}] ", severity)?;
+        }
         write!(f, "{}", error)?;
+        let mut indented = Indented {
+            inner: f,
+            number: None,
+            started: true,
+        };
+        write_location(&mut indented, error, Self::head_location(this))?;
+        write_layer_backtrace(&mut indented, Some(this))?;
+        write_causes(f, this, error, "\n\n")?;
 
-        if let Some(cause) = error.source() {
-            write!(f, "\n\nCaused by:")?;
-            let multiple = cause.source().is_some();
-            for (n, error) in Chain::new(cause).enumerate() {
-                writeln!(f)?;
-                let mut indented = Indented {
-                    inner: f,
-                    number: if multiple { Some(n) } else { None },
-                    started: false,
-                };
-                write!(indented, "{}", error)?;
+        // An error rebuilt from a remote one shows the backtrace captured where
+        // it originated.
+        #[cfg(all(feature = "std", feature = "serde"))]
+        {
+            if let Some(backtrace) = crate::remote::backtrace(error) {
+                let backtrace = crate::frames::short(backtrace);
+                return write!(f, "\n\nStack backtrace:\n{}", backtrace.trim_end());
             }
         }
 
-        #[cfg(any(backtrace, feature = "backtrace"))]
+        #[cfg(any(std_backtrace, feature = "backtrace"))]
         {
-            use crate::backtrace::BacktraceStatus;
-
-            let backtrace = Self::backtrace(this);
-            if let BacktraceStatus::Captured = backtrace.status() {
-                let mut backtrace = backtrace.to_string();
-                write!(f, "\n\n")?;
-                if backtrace.starts_with("stack backtrace:") {
-                    // Capitalize to match "Caused by:"
-                    backtrace.replace_range(0..1, "S");
-                } else {
-                    // "stack backtrace:" prefix was removed in
-                    // https://github.com/rust-lang/backtrace-rs/pull/286
-                    writeln!(f, "Stack backtrace:")?;
-                }
-                backtrace.truncate(backtrace.trim_end().len());
-                write!(f, "{}", backtrace)?;
+            if let Some(backtrace) = crate::frames::printed_of(this) {
+                write!(f, "\n\nStack backtrace:\n{}", backtrace.trim_end())?;
             }
         }
 
         Ok(())
     }
+
+    // The location of the outermost layer that recorded one. Layers that only
+    // wrap a shared error defer to the layers of the shared error.
+    pub(crate) unsafe fn head_location(this: Ref<Self>) -> Option<&'static Location> {
+        Self::layers(this).find_map(|layer| Self::location(layer))
+    }
+
+    // The layer whose own error is `error`, if `error` is one of the layers of
+    // this anyhow::Error rather than an ordinary source.
+    fn layer_of<'a>(
+        owned: &mut Owned<'a>,
+        error: &(dyn StdError + 'static),
+    ) -> Option<Ref<'a, Self>> {
+        match owned.take(error) {
+            Some(owned) if owned.head => Some(owned.layer),
+            _ => None,
+        }
+    }
+}
+
+// Errors rebuilt from a remote one show the location recorded by the original
+// error in place of their own, and panics show the location of the panic.
+fn write_location(
+    f: &mut dyn Write,
+    error: &(dyn StdError + 'static),
+    location: Option<&Location>,
+) -> fmt::Result {
+    if !crate::location::shown() {
+        return Ok(());
+    }
+
+    #[cfg(feature = "std")]
+    {
+        if let Some(recorded) = recorded_location(error) {
+            return match recorded {
+                Some(location) => write!(f, "\nat {}", location),
+                None => Ok(()),
+            };
+        }
+    }
+
+    let _ = error;
+    match location {
+        Some(location) => write!(f, "\nat {}", location),
+        None => Ok(()),
+    }
+}
+
+// The location recorded by an error that stands in for another one, if error
+// is such an error.
+#[cfg(feature = "std")]
+fn recorded_location<'a>(error: &'a (dyn StdError + 'static)) -> Option<Option<&'a str>> {
+    #[cfg(feature = "serde")]
+    {
+        if let Some(remote) = error.downcast_ref::<RemoteError>() {
+            return Some(remote.location());
+        }
+    }
+
+    error.downcast_ref::<PanicError>().map(PanicError::location)
+}
+
+// The first frames of the backtrace captured where a layer of context was
+// added, if enabled by set_context_backtraces.
+unsafe fn write_layer_backtrace(f: &mut dyn Write, layer: Option<Ref<ErrorImpl>>) -> fmt::Result {
+    #[cfg(all(feature = "std", any(std_backtrace, feature = "backtrace")))]
+    {
+        use crate::context::LayerBacktrace;
+
+        let captured = layer.and_then(|layer| ErrorImpl::own_attachment::<LayerBacktrace>(layer));
+        if let Some(captured) = captured {
+            #[cfg(feature = "backtrace")]
+            let frames = captured.backtrace.leading(captured.frames);
+            #[cfg(not(feature = "backtrace"))]
+            let frames = crate::frames::leading(
+                &crate::frames::printed(&captured.backtrace),
+                captured.frames,
+            );
+            if !frames.is_empty() {
+                write!(f, "\n{}", frames.trim_end())?;
+            }
+        }
+    }
+
+    let _ = (f, layer);
+    Ok(())
+}
+
+// Writes the "Caused by:" section for the given error. Errors created by
+// Error::aggregate list their children here instead, each child followed by
+// its own causes, nested one level deeper.
+unsafe fn write_causes(
+    f: &mut dyn Write,
+    layers: Ref<ErrorImpl>,
+    error: &(dyn StdError + 'static),
+    separator: &str,
+) -> fmt::Result {
+    #[cfg(feature = "std")]
+    {
+        if let Some(aggregate) = error.downcast_ref::<AggregateError>() {
+            return write_children(f, &aggregate.errors, separator);
+        }
+    }
+
+    if let Some(cause) = error.source() {
+        let mut owned = ErrorImpl::owned(layers);
+        owned.take(error);
+        write!(f, "{}Caused by:", separator)?;
+        let multiple = cause.source().is_some();
+        for (n, error) in Chain::new(cause).enumerate() {
+            writeln!(f)?;
+            let mut indented = Indented {
+                inner: f,
+                number: if multiple { Some(n) } else { None },
+                started: false,
+            };
+            write!(indented, "{}", error)?;
+            let layer = ErrorImpl::layer_of(&mut owned, error);
+            let location = layer.and_then(|layer| ErrorImpl::head_location(layer));
+            write_location(&mut indented, error, location)?;
+            write_layer_backtrace(&mut indented, layer)?;
+
+            #[cfg(feature = "std")]
+            {
+                if let Some(aggregate) = error.downcast_ref::<AggregateError>() {
+                    write_children(&mut indented, &aggregate.errors, "\n")?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+fn write_children(f: &mut dyn Write, children: &[Error], separator: &str) -> fmt::Result {
+    if children.is_empty() {
+        return Ok(());
+    }
+
+    write!(f, "{}Caused by:", separator)?;
+    let multiple = children.len() > 1;
+    for (n, child) in children.iter().enumerate() {
+        writeln!(f)?;
+        let mut indented = Indented {
+            inner: f,
+            number: if multiple { Some(n) } else { None },
+            started: false,
+        };
+        let error: &(dyn StdError + 'static) = &**child;
+        let layers = child.inner.by_ref();
+        write!(indented, "{}", error)?;
+        let location = unsafe { ErrorImpl::head_location(layers) };
+        write_location(&mut indented, error, location)?;
+        unsafe { write_layer_backtrace(&mut indented, Some(layers))? };
+        unsafe { write_causes(&mut indented, layers, error, "\n")? };
+    }
+
+    Ok(())
 }
 
-struct Indented<'a, D> {
+struct Indented<'a, D: ?Sized> {
     inner: &'a mut D,
     number: Option<usize>,
     started: bool,
@@ -72,7 +233,7 @@ struct Indented<'a, D> {
 
 impl<T> Write for Indented<'_, T>
 where
-    T: Write,
+    T: ?Sized + Write,
 {
     fn write_str(&mut self, s: &str) -> fmt::Result {
         for (i, line) in s.split('\n').enumerate() {
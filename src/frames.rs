@@ -0,0 +1,363 @@
+#[cfg(any(std_backtrace, feature = "backtrace"))]
+use crate::backtrace::Backtrace;
+use crate::error::ErrorImpl;
+use crate::ptr::Ref;
+#[cfg(feature = "backtrace")]
+use crate::{BacktraceFrame, BacktraceSymbol};
+use core::fmt::{self, Write};
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::borrow::Cow;
+use std::env;
+use std::sync::{PoisonError, RwLock};
+
+// The functions of the backtrace of the error, innermost first, including
+// those inlined into a frame. The frames captured with the "backtrace" feature
+// have them by name; the standard library's backtrace only has them as text,
+// as does an error rebuilt from a remote one, which reports the backtrace
+// captured where it originated.
+pub(crate) unsafe fn function_names(this: Ref<'_, ErrorImpl>) -> Option<Vec<String>> {
+    #[cfg(feature = "serde")]
+    {
+        if let Some(backtrace) = crate::remote::backtrace(ErrorImpl::error(this)) {
+            return Some(printed_function_names(backtrace));
+        }
+    }
+
+    #[cfg(feature = "backtrace")]
+    {
+        if let Some(frames) = ErrorImpl::frames(this) {
+            let names: Vec<String> = frames
+                .frames()
+                .flat_map(BacktraceFrame::symbols)
+                .filter_map(BacktraceSymbol::name)
+                .collect();
+            if !names.is_empty() {
+                return Some(names);
+            }
+        }
+    }
+
+    #[cfg(std_backtrace)]
+    {
+        use crate::backtrace::BacktraceStatus;
+
+        let backtrace = ErrorImpl::backtrace(this);
+        if let BacktraceStatus::Captured = backtrace.status() {
+            return Some(printed_function_names(&backtrace.to_string()));
+        }
+    }
+
+    let _ = this;
+    None
+}
+
+// The functions of a printed backtrace. The text has the same shape for
+// std::backtrace and for RemoteError:
+//
+//        0: anyhow::error::<impl anyhow::Error>::msg
+//                  at ./src/error.rs:83:36
+//        1: core::ops::function::FnOnce::call_once
+//                  at /rustc/.../library/core/src/ops/function.rs:250:5
+//           test::__rust_begin_short_backtrace
+//                  at /rustc/.../library/test/src/lib.rs:733:18
+fn printed_function_names(backtrace: &str) -> Vec<String> {
+    let (_head, frames) = split_frames(backtrace);
+    frames
+        .into_iter()
+        .flatten()
+        .map(str::trim_start)
+        .filter(|line| !line.is_empty() && !line.starts_with("at "))
+        .map(|line| split_index(line).map_or(line, |(_, function)| function))
+        .map(str::to_owned)
+        .collect()
+}
+
+fn split_index(line: &str) -> Option<(usize, &str)> {
+    let mut parts = line.splitn(2, ": ");
+    match (parts.next(), parts.next()) {
+        (Some(index), Some(function)) => match index.parse() {
+            Ok(index) => Some((index, function)),
+            Err(_) => None,
+        },
+        _ => None,
+    }
+}
+
+pub(crate) fn set_collapsed(crates: &'static [&'static str]) {
+    *collapsed().write().unwrap_or_else(PoisonError::into_inner) = crates;
+}
+
+fn collapsed() -> &'static RwLock<&'static [&'static str]> {
+    static COLLAPSED: AtomicPtr<RwLock<&'static [&'static str]>> = AtomicPtr::new(ptr::null_mut());
+
+    let collapsed = COLLAPSED.load(Ordering::Acquire);
+    if !collapsed.is_null() {
+        return unsafe { &*collapsed };
+    }
+    let new = Box::into_raw(Box::new(RwLock::new(&[][..])));
+    match COLLAPSED.compare_exchange(ptr::null_mut(), new, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => unsafe { &*new },
+        Err(collapsed) => {
+            // Another thread got there first.
+            drop(unsafe { Box::from_raw(new) });
+            unsafe { &*collapsed }
+        }
+    }
+}
+
+// Whether backtraces are printed in full, as asked for with
+// RUST_LIB_BACKTRACE=full or RUST_BACKTRACE=full. Like the variables that
+// enable capturing backtraces, these are only read once.
+pub(crate) fn full() -> bool {
+    static FULL: AtomicUsize = AtomicUsize::new(0);
+    match FULL.load(Ordering::Relaxed) {
+        0 => {}
+        1 => return false,
+        _ => return true,
+    }
+    let full = match env::var_os("RUST_LIB_BACKTRACE") {
+        Some(s) => s == "full",
+        None => env::var_os("RUST_BACKTRACE").map_or(false, |s| s == "full"),
+    };
+    FULL.store(full as usize + 1, Ordering::Relaxed);
+    full
+}
+
+// What the short form of a backtrace keeps of its frames. It starts at the
+// frame where the error was created, leaving out the frames of anyhow and of
+// the standard library leading up to it, and ends at main or at the closure a
+// thread was spawned with, and its frames are numbered from 0 at the first
+// one. Consecutive frames of the crates passed to set_collapsed_crates are
+// folded into a single line.
+pub(crate) struct Short {
+    pub start: usize,
+    pub parts: Vec<Part>,
+    pub omitted: bool,
+}
+
+pub(crate) enum Part {
+    // The frame at this index.
+    Frame(usize),
+    // This many frames of a collapsed crate.
+    Collapsed(&'static str, usize),
+}
+
+// Picks the frames of the short form of a backtrace by the functions of each
+// of its frames, innermost first, including those inlined into the frame.
+pub(crate) fn select<S>(frames: &[Vec<S>]) -> Short
+where
+    S: AsRef<str>,
+{
+    let library = |frame: &[S]| frame.iter().all(|function| is_library(function.as_ref()));
+    let of_crate = |frame: &[S], krate: &str| {
+        !frame.is_empty()
+            && frame
+                .iter()
+                .all(|function| in_crate(function.as_ref(), krate))
+    };
+
+    let start = frames.iter().position(|frame| !library(frame)).unwrap_or(0);
+    let mut end = frames.len();
+    if let Some(entry) = frames[start..]
+        .iter()
+        .position(|frame| frame.iter().any(|function| is_entry(function.as_ref())))
+    {
+        // Along with the shims between main and the runtime that calls it.
+        end = start + entry;
+        while end > start + 1 && library(&frames[end - 1]) {
+            end -= 1;
+        }
+    }
+
+    let collapsed = *collapsed().read().unwrap_or_else(PoisonError::into_inner);
+    let mut short = Short {
+        start,
+        parts: Vec::new(),
+        omitted: start > 0 || end < frames.len(),
+    };
+    let mut i = start;
+    while i < end {
+        let krate = collapsed.iter().find(|krate| of_crate(&frames[i], krate));
+        match krate {
+            Some(krate) => {
+                let n = frames[i..end]
+                    .iter()
+                    .take_while(|frame| of_crate(frame, krate))
+                    .count();
+                short.parts.push(Part::Collapsed(krate, n));
+                short.omitted = true;
+                i += n;
+            }
+            None => {
+                short.parts.push(Part::Frame(i));
+                i += 1;
+            }
+        }
+    }
+    short
+}
+
+pub(crate) fn write_collapsed(f: &mut dyn Write, krate: &str, n: usize) -> fmt::Result {
+    let plural = if n == 1 { "" } else { "s" };
+    writeln!(f, "      [... {} frame{} of {} omitted]", n, plural, krate)
+}
+
+pub(crate) const OMITTED: &str =
+    "note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace.";
+
+// The short form of a backtrace printed as text by the standard library or
+// received from a remote error, unless full backtraces were asked for.
+pub(crate) fn short(backtrace: &str) -> Cow<'_, str> {
+    if full() {
+        return Cow::Borrowed(backtrace);
+    }
+
+    let (head, frames) = split_frames(backtrace);
+    let functions: Vec<Vec<&str>> = frames
+        .iter()
+        .map(|frame| functions(frame).collect())
+        .collect();
+    let selected = select(&functions);
+
+    let mut short = String::new();
+    for line in head {
+        short.push_str(line);
+        short.push('\n');
+    }
+    for part in &selected.parts {
+        match *part {
+            Part::Frame(i) => {
+                // Number the frames from where the error was created, keeping
+                // the gaps left by collapsed frames.
+                let (numbered, rest) = frames[i].split_first().unwrap();
+                match split_index(numbered.trim_start()) {
+                    Some((_index, function)) => {
+                        let _ = writeln!(short, "{:4}: {}", i - selected.start, function);
+                    }
+                    None => {
+                        short.push_str(numbered);
+                        short.push('\n');
+                    }
+                }
+                for line in rest {
+                    short.push_str(line);
+                    short.push('\n');
+                }
+            }
+            Part::Collapsed(krate, n) => {
+                let _ = write_collapsed(&mut short, krate, n);
+            }
+        }
+    }
+    if selected.omitted {
+        short.push_str(OMITTED);
+        short.push('\n');
+    }
+    Cow::Owned(short)
+}
+
+// A backtrace captured by anyhow, in short form. The backtrace crate's ones
+// print in short form by themselves.
+#[cfg(any(std_backtrace, feature = "backtrace"))]
+pub(crate) fn printed(backtrace: &Backtrace) -> String {
+    let backtrace = backtrace.to_string();
+    #[cfg(std_backtrace)]
+    return short(&backtrace).into_owned();
+    #[cfg(not(std_backtrace))]
+    return backtrace;
+}
+
+// The backtrace of the error in short form, if one was captured. With the
+// "backtrace" feature, that is the one captured with the frames, unless the
+// error was given a backtrace of the standard library's type.
+#[cfg(any(std_backtrace, feature = "backtrace"))]
+pub(crate) unsafe fn printed_of(this: Ref<'_, ErrorImpl>) -> Option<String> {
+    #[cfg(all(std_backtrace, feature = "backtrace"))]
+    {
+        if let Some(frames) = ErrorImpl::frames(this) {
+            if frames.is_captured() {
+                return Some(frames.to_string());
+            }
+        }
+    }
+
+    use crate::backtrace::BacktraceStatus;
+
+    let backtrace = ErrorImpl::backtrace(this);
+    match backtrace.status() {
+        BacktraceStatus::Captured => Some(printed(backtrace)),
+        _ => None,
+    }
+}
+
+// The first frames of the short form of a backtrace of the standard library.
+#[cfg(all(std_backtrace, not(feature = "backtrace")))]
+pub(crate) fn leading(short: &str, limit: usize) -> String {
+    let (_head, frames) = split_frames(short);
+    let mut leading = String::new();
+    for line in frames.iter().take(limit).flatten() {
+        if !line.starts_with("note: ") {
+            leading.push_str(line);
+            leading.push('\n');
+        }
+    }
+    leading
+}
+
+// The lines before the first frame, and the lines of each frame: its numbered
+// line, followed by its location and by the functions inlined into it along
+// with their locations.
+fn split_frames(backtrace: &str) -> (Vec<&str>, Vec<Vec<&str>>) {
+    let mut head = Vec::new();
+    let mut frames: Vec<Vec<&str>> = Vec::new();
+    for line in backtrace.lines() {
+        if split_index(line.trim_start()).is_some() {
+            frames.push(vec![line]);
+        } else if let Some(frame) = frames.last_mut() {
+            frame.push(line);
+        } else {
+            head.push(line);
+        }
+    }
+    (head, frames)
+}
+
+fn functions<'a>(frame: &'a [&'a str]) -> impl Iterator<Item = &'a str> + 'a {
+    frame
+        .iter()
+        .map(|line| line.trim_start())
+        .filter(|line| !line.starts_with("at "))
+        .map(|line| split_index(line).map_or(line, |(_, function)| function))
+}
+
+fn in_crate(function: &str, krate: &str) -> bool {
+    let function = function.trim_start_matches(|c| c == '<' || c == '&');
+    function.starts_with(krate) && function[krate.len()..].starts_with("::")
+}
+
+// Frames of anyhow itself and of the backtrace capture.
+pub(crate) fn is_internal(function: &str) -> bool {
+    function.starts_with("anyhow::")
+        || function.contains("<anyhow::")
+        || function.contains(" anyhow::")
+        || function.starts_with("backtrace::")
+        || function.starts_with("<backtrace::")
+        || function.starts_with("std::backtrace")
+        || function.starts_with("<std::backtrace")
+}
+
+// Frames of anyhow and of the standard library, including the shims that call
+// closures and function pointers.
+fn is_library(function: &str) -> bool {
+    is_internal(function)
+        || function.contains(" as core::ops::function::")
+        || in_crate(function, "core")
+        || in_crate(function, "alloc")
+        || in_crate(function, "std")
+}
+
+// The runtime frame that calls main, or the closure of a spawned thread.
+fn is_entry(function: &str) -> bool {
+    function.contains("__rust_begin_short_backtrace") || in_crate(function, "std::rt")
+}
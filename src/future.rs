@@ -0,0 +1,126 @@
+use crate::context::ext::StdError;
+use crate::scope::{self, Scope};
+use crate::{Error, FutureContext, FutureExt, FutureWithContext, InErrorScope};
+use core::fmt::Display;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{self, Poll};
+
+impl<Fut> FutureExt for Fut
+where
+    Fut: Future,
+{
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    fn context<C>(self, context: C) -> FutureContext<Self, C>
+    where
+        C: Display + Send + Sync + 'static,
+    {
+        FutureContext {
+            future: self,
+            context: Some(context),
+            location: location!(),
+        }
+    }
+
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    fn with_context<C, F>(self, context: F) -> FutureWithContext<Self, F>
+    where
+        C: Display + Send + Sync + 'static,
+        F: FnOnce() -> C,
+    {
+        FutureWithContext {
+            future: self,
+            context: Some(context),
+            location: location!(),
+        }
+    }
+
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    fn in_error_scope<C, F>(self, context: F) -> InErrorScope<Self>
+    where
+        C: Display,
+        F: FnOnce() -> C,
+    {
+        InErrorScope {
+            future: self,
+            scope: Scope::new(context()),
+        }
+    }
+}
+
+impl<Fut, T, E, C> Future for FutureContext<Fut, C>
+where
+    Fut: Future<Output = Result<T, E>>,
+    E: StdError + Send + Sync + 'static,
+    C: Display + Send + Sync + 'static,
+{
+    type Output = Result<T, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context) -> Poll<Self::Output> {
+        // Safety: the future is structurally pinned, and neither moved out of
+        // nor dropped other than in place. The context is not pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        match future.poll(cx) {
+            Poll::Ready(Ok(ok)) => Poll::Ready(Ok(ok)),
+            Poll::Ready(Err(error)) => {
+                let context = this.context.take().expect(POLLED_AFTER_COMPLETION);
+                Poll::Ready(Err(error.ext_context_at(context, this.location)))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<Fut, T, E, C, F> Future for FutureWithContext<Fut, F>
+where
+    Fut: Future<Output = Result<T, E>>,
+    E: StdError + Send + Sync + 'static,
+    C: Display + Send + Sync + 'static,
+    F: FnOnce() -> C,
+{
+    type Output = Result<T, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context) -> Poll<Self::Output> {
+        // Safety: as for FutureContext.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        match future.poll(cx) {
+            Poll::Ready(Ok(ok)) => Poll::Ready(Ok(ok)),
+            Poll::Ready(Err(error)) => {
+                let context = this.context.take().expect(POLLED_AFTER_COMPLETION);
+                Poll::Ready(Err(error.ext_context_at(context(), this.location)))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<Fut> Future for InErrorScope<Fut>
+where
+    Fut: Future,
+{
+    type Output = Fut::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context) -> Poll<Self::Output> {
+        // Safety: the future is structurally pinned, and neither moved out of
+        // nor dropped other than in place. The scope is not pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        // The scope is entered on whichever thread polls the future, and only
+        // for as long as it is being polled.
+        let _guard = scope::enter(&this.scope);
+        future.poll(cx)
+    }
+}
+
+const POLLED_AFTER_COMPLETION: &str = "future polled after completion";
+
+pub(crate) mod private {
+    use core::future::Future;
+
+    pub trait Sealed {}
+
+    impl<Fut> Sealed for Fut where Fut: Future {}
+}
@@ -0,0 +1,60 @@
+use crate::{Error, SetHookError, StdError};
+use core::fmt::{self, Display};
+use core::mem;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+use std::cell::Cell;
+
+// The installed hook as a type-erased function pointer, or null.
+static HOOK: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+thread_local! {
+    // Set while the hook runs on this thread, so that errors created by the
+    // hook itself, for example by calling `context`, do not invoke it again.
+    static RUNNING: Cell<bool> = Cell::new(false);
+}
+
+pub(crate) fn set(hook: fn(&Error)) -> Result<(), SetHookError> {
+    let hook = hook as *mut ();
+    match HOOK.compare_exchange(ptr::null_mut(), hook, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(SetHookError { _private: () }),
+    }
+}
+
+pub(crate) fn invoke(error: &Error) {
+    let hook = HOOK.load(Ordering::Acquire);
+    if hook.is_null() {
+        return;
+    }
+
+    // Safety: the only non-null value ever stored is a fn(&Error).
+    let hook = unsafe { mem::transmute::<*mut (), fn(&Error)>(hook) };
+
+    // The thread local is unavailable while the thread is being torn down, in
+    // which case the hook is skipped.
+    let _ = RUNNING.try_with(|running| {
+        if running.replace(true) {
+            return;
+        }
+
+        struct Reset<'a>(&'a Cell<bool>);
+
+        impl Drop for Reset<'_> {
+            fn drop(&mut self) {
+                self.0.set(false);
+            }
+        }
+
+        let _reset = Reset(running);
+        hook(error);
+    });
+}
+
+impl Display for SetHookError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an anyhow hook has already been installed")
+    }
+}
+
+impl StdError for SetHookError {}
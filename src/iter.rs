@@ -0,0 +1,145 @@
+use crate::context::ext::StdError;
+use crate::{ContextEach, Error, IteratorExt};
+use core::fmt::Display;
+use core::iter::FromIterator;
+
+impl<I> IteratorExt for I
+where
+    I: Iterator,
+{
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    fn context_each<C, F>(self, context: F) -> ContextEach<Self, F>
+    where
+        C: Display + Send + Sync + 'static,
+        F: FnMut(usize) -> C,
+    {
+        ContextEach {
+            inner: self,
+            context,
+            index: 0,
+            location: location!(),
+        }
+    }
+
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    fn try_collect_all<B, T, E>(self) -> Result<B, Error>
+    where
+        Self: Iterator<Item = Result<T, E>>,
+        E: StdError + Send + Sync + 'static,
+        B: FromIterator<T>,
+    {
+        let mut errors = Vec::new();
+        let collected = self
+            .context_each(|index| format!("item {}", index))
+            .filter_map(|item| item.map_err(|error| errors.push(error)).ok())
+            .collect();
+        if errors.is_empty() {
+            Ok(collected)
+        } else {
+            Err(Error::aggregate(errors))
+        }
+    }
+}
+
+impl<I, T, E, C, F> Iterator for ContextEach<I, F>
+where
+    I: Iterator<Item = Result<T, E>>,
+    E: StdError + Send + Sync + 'static,
+    C: Display + Send + Sync + 'static,
+    F: FnMut(usize) -> C,
+{
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.inner.next()?;
+        Some(self.wrap(item))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<I, F> ContextEach<I, F> {
+    // Wraps the error of the item at the current index, counting items that
+    // succeeded as well as those that failed.
+    fn wrap<T, E, C>(&mut self, item: Result<T, E>) -> Result<T, Error>
+    where
+        E: StdError + Send + Sync + 'static,
+        C: Display + Send + Sync + 'static,
+        F: FnMut(usize) -> C,
+    {
+        let index = self.index;
+        self.index += 1;
+        match item {
+            Ok(ok) => Ok(ok),
+            Err(error) => Err(error.ext_context_at((self.context)(index), self.location)),
+        }
+    }
+}
+
+#[cfg(feature = "futures-core")]
+mod stream {
+    use super::*;
+    use crate::StreamExt;
+    use core::pin::Pin;
+    use core::task::{Context, Poll};
+    use futures_core::Stream;
+
+    impl<S> StreamExt for S
+    where
+        S: Stream,
+    {
+        #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+        fn context_each<C, F>(self, context: F) -> ContextEach<Self, F>
+        where
+            C: Display + Send + Sync + 'static,
+            F: FnMut(usize) -> C,
+        {
+            ContextEach {
+                inner: self,
+                context,
+                index: 0,
+                location: location!(),
+            }
+        }
+    }
+
+    impl<S, T, E, C, F> Stream for ContextEach<S, F>
+    where
+        S: Stream<Item = Result<T, E>>,
+        E: StdError + Send + Sync + 'static,
+        C: Display + Send + Sync + 'static,
+        F: FnMut(usize) -> C,
+    {
+        type Item = Result<T, Error>;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+            // Safety: the stream is structurally pinned, and neither moved out
+            // of nor dropped other than in place. The rest is not pinned.
+            let this = unsafe { self.get_unchecked_mut() };
+            let inner = unsafe { Pin::new_unchecked(&mut this.inner) };
+            match inner.poll_next(cx) {
+                Poll::Ready(Some(item)) => Poll::Ready(Some(this.wrap(item))),
+                Poll::Ready(None) => Poll::Ready(None),
+                Poll::Pending => Poll::Pending,
+            }
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            self.inner.size_hint()
+        }
+    }
+}
+
+pub(crate) mod private {
+    pub trait Sealed {}
+
+    impl<I> Sealed for I where I: Iterator {}
+
+    #[cfg(feature = "futures-core")]
+    pub trait SealedStream {}
+
+    #[cfg(feature = "futures-core")]
+    impl<S> SealedStream for S where S: futures_core::Stream {}
+}
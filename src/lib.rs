@@ -128,11 +128,11 @@
 //!   # ;
 //!   ```
 //!
-//! - If using the nightly channel, or stable with `features = ["backtrace"]`, a
-//!   backtrace is captured and printed with the error if the underlying error
-//!   type does not already provide its own. In order to see backtraces, they
-//!   must be enabled through the environment variables described in
-//!   [`std::backtrace`]:
+//! - If using Rust 1.65 or newer, or older compilers with
+//!   `features = ["backtrace"]`, a backtrace is captured and printed with the
+//!   error if the underlying error type does not already provide its own. In
+//!   order to see backtraces, they must be enabled through the environment
+//!   variables described in [`std::backtrace`]:
 //!
 //!   - If you want panics and errors to both have backtraces, set
 //!     `RUST_BACKTRACE=1`;
@@ -140,10 +140,11 @@
 //!   - If you want only panics to have backtraces, set `RUST_BACKTRACE=1` and
 //!     `RUST_LIB_BACKTRACE=0`.
 //!
-//!   The tracking issue for this feature is [rust-lang/rust#53487].
+//!   Backtraces leave out the frames of anyhow and of the Rust runtime unless
+//!   the variable is set to `full`, and can fold the frames of other crates,
+//!   see [`set_collapsed_crates`].
 //!
 //!   [`std::backtrace`]: https://doc.rust-lang.org/std/backtrace/index.html#environment-variables
-//!   [rust-lang/rust#53487]: https://github.com/rust-lang/rust/issues/53487
 //!
 //! - Anyhow works with any error type that has an impl of `std::error::Error`,
 //!   including ones defined in your crate. We do not bundle a `derive(Error)`
@@ -194,6 +195,40 @@
 //!
 //! <br>
 //!
+//! # Serialization
+//!
+//! With the optional "serde" feature enabled, `anyhow::Error` and `Chain`
+//! implement `serde::Serialize`. An error serializes as its message, the
+//! message, Rust type name and source location of each of its causes in
+//! order, and its backtrace if one was captured. The backtrace is a list of
+//! frames with the "backtrace" feature, which captures them one by one, and
+//! otherwise the text of the backtrace as it is printed.
+//!
+//! ```json
+//! {
+//!   "message": "Failed to read instrs from ./path/to/instrs.json",
+//!   "type_name": "alloc::string::String",
+//!   "location": "src/main.rs:12:10",
+//!   "causes": [
+//!     {
+//!       "message": "No such file or directory (os error 2)",
+//!       "type_name": "std::io::error::Error",
+//!       "location": null
+//!     }
+//!   ],
+//!   "backtrace": null
+//! }
+//! ```
+//!
+//! Type names are reported for each value that went into the error through
+//! `Error::new`, `anyhow!`, `?` or `context`, and are `null` for causes that
+//! were only reachable through some other error's `source()`.
+//!
+//! On the receiving end, `RemoteError` deserializes from the same format and
+//! rebuilds the chain of causes.
+//!
+//! <br>
+//!
 //! # No-std support
 //!
 //! In no_std mode, the same API is almost all available and works the same way.
@@ -211,7 +246,7 @@
 //! non-Anyhow error type inside a function that returns Anyhow's error type.
 
 #![doc(html_root_url = "https://docs.rs/anyhow/1.0.66")]
-#![cfg_attr(backtrace, feature(error_generic_member_access, provide_any))]
+#![cfg_attr(error_generic_member_access, feature(error_generic_member_access))]
 #![cfg_attr(doc_cfg, feature(doc_cfg))]
 #![cfg_attr(not(feature = "std"), no_std)]
 #![deny(dead_code, unused_imports, unused_mut)]
@@ -237,14 +272,43 @@ extern crate alloc;
 
 #[macro_use]
 mod backtrace;
+#[macro_use]
+mod location;
+#[cfg(feature = "std")]
+mod aggregate;
 mod chain;
 mod context;
 mod ensure;
 mod error;
+#[cfg(feature = "std")]
+mod field;
+mod fingerprint;
 mod fmt;
+#[cfg(feature = "std")]
+mod frames;
+#[cfg(feature = "std")]
+mod future;
+#[cfg(feature = "std")]
+mod hook;
+#[cfg(feature = "std")]
+mod iter;
 mod kind;
 mod macros;
+#[cfg(feature = "std")]
+mod panic;
 mod ptr;
+#[cfg(all(feature = "std", feature = "serde"))]
+mod remote;
+mod report;
+#[cfg(feature = "std")]
+mod scope;
+#[cfg(feature = "serde")]
+mod serialize;
+mod severity;
+#[cfg(feature = "std")]
+mod shared;
+#[cfg(feature = "backtrace")]
+mod unresolved;
 mod wrapper;
 
 use crate::error::ErrorImpl;
@@ -346,7 +410,8 @@ pub use anyhow as format_err;
 /// ```
 ///
 /// If none of the built-in representations are appropriate and you would prefer
-/// to render the error and its cause chain yourself, it can be done something
+/// to render the error and its cause chain yourself, install a
+/// [`ReportHandler`] to replace the Debug representation, or do it something
 /// like this:
 ///
 /// ```
@@ -396,6 +461,436 @@ pub struct Error {
 #[derive(Clone)]
 pub struct Chain<'a> {
     state: crate::chain::ChainState<'a>,
+    #[cfg(feature = "serde")]
+    layers: Option<crate::ptr::Ref<'a, crate::error::ErrorImpl>>,
+}
+
+/// A key-value pair of context on an error.
+///
+/// Fields are added with [`Error::with_field`] or [`Context::context_kv`],
+/// and enumerated across the whole chain of an error with
+/// [`Error::fields`]. Each keeps its value with its own type, for a structured
+/// logger to render as it sees fit, and displays as `key=value`.
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub struct Field {
+    key: &'static str,
+    value: Box<dyn crate::field::Value>,
+}
+
+/// Iterator of the fields of an error and of its causes, outermost first.
+///
+/// Returned by [`Error::fields`].
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub struct Fields<'a> {
+    chain: Chain<'a>,
+    layer: core::slice::Iter<'a, Field>,
+}
+
+/// A reference-counted, cloneable handle to an [`Error`].
+///
+/// `anyhow::Error` is uniquely owned and cannot be cloned. Converting it into
+/// a `SharedError` moves it behind an `Arc` so that the same failure can be
+/// handed to any number of consumers, for example every waiter on a memoized
+/// computation that failed.
+///
+/// `SharedError` dereferences to `Error`, so the usual accessors such as
+/// [`downcast_ref`][Error::downcast_ref], [`chain`][Error::chain],
+/// [`root_cause`][Error::root_cause] and the backtrace are available through
+/// it. It renders identically to the error it was created from.
+///
+/// A `SharedError` can be converted back into an `anyhow::Error`. If no other
+/// clones are alive, this gives back the original error unchanged. Otherwise
+/// the resulting error refers to the shared one: it still renders the same
+/// and still supports `downcast_ref`, but downcasting by value or by mutable
+/// reference only succeeds for `SharedError` itself.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::{anyhow, SharedError};
+/// use std::io;
+///
+/// let error = anyhow!(io::Error::new(io::ErrorKind::Other, "oh no!"));
+/// let shared: SharedError = error.into_shared();
+///
+/// let waiters = vec![shared.clone(), shared.clone()];
+/// for waiter in &waiters {
+///     assert!(waiter.downcast_ref::<io::Error>().is_some());
+/// }
+///
+/// let error = anyhow::Error::from(shared);
+/// assert_eq!(error.to_string(), "oh no!");
+/// ```
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+#[derive(Clone)]
+pub struct SharedError {
+    inner: std::sync::Arc<Error>,
+}
+
+/// An error rebuilt from the serialized form of an `anyhow::Error`.
+///
+/// With the "serde" feature, an `anyhow::Error` serializes to its message, its
+/// causes, their Rust type names and source locations, and its backtrace.
+/// `RemoteError` deserializes from that same form, for example on the other
+/// side of a process boundary, and reproduces the original chain of errors:
+/// each cause is a `RemoteError` of its own, reachable through
+/// `std::error::Error::source`.
+///
+/// Wrapped in an `anyhow::Error`, a `RemoteError` renders with `{}`, `{:#}`
+/// and `{:?}` the same way that the original error did, including its source
+/// locations and backtrace.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::{Context, RemoteError};
+/// use std::io;
+///
+/// let error = Err::<(), _>(io::Error::new(io::ErrorKind::Other, "oh no!"))
+///     .context("failed to read config")
+///     .unwrap_err();
+/// let json = serde_json::to_string(&error)?;
+///
+/// let remote: RemoteError = serde_json::from_str(&json)?;
+/// let error = anyhow::Error::new(remote);
+/// assert_eq!(format!("{:#}", error), "failed to read config: oh no!");
+///
+/// for cause in error.chain() {
+///     let cause = cause.downcast_ref::<RemoteError>().unwrap();
+///     if cause.is::<io::Error>() {
+///         println!("io error: {}", cause);
+///     }
+/// }
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[cfg(all(feature = "std", feature = "serde"))]
+#[cfg_attr(doc_cfg, doc(cfg(all(feature = "std", feature = "serde"))))]
+pub struct RemoteError {
+    message: String,
+    type_name: Option<String>,
+    location: Option<String>,
+    backtrace: Option<String>,
+    source: Option<Box<RemoteError>>,
+}
+
+/// An error that was raised as a panic.
+///
+/// Errors created by [`Error::from_panic`] and [`catch_unwind`] hold a
+/// `PanicError` carrying the message that the panic was raised with and, if it
+/// is known, the source location of the panic. The `Debug` representation of
+/// such an error shows the location of the panic in place of the location at
+/// which the `anyhow::Error` was created, and the backtrace captured when the
+/// panic was raised.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::PanicError;
+///
+/// let error = anyhow::catch_unwind(|| -> anyhow::Result<()> {
+///     panic!("worker crashed");
+/// })
+/// .unwrap_err();
+///
+/// let panic = error.downcast_ref::<PanicError>().unwrap();
+/// assert_eq!(panic.message(), "worker crashed");
+/// println!("panicked at {}", panic.location().unwrap());
+/// ```
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub struct PanicError {
+    message: String,
+    location: Option<String>,
+}
+
+/// A stable hash of an error, for grouping occurrences of the same failure.
+///
+/// A fingerprint is computed from the Rust type name and the message of each
+/// error in the [chain][Error::chain], and optionally from the innermost few
+/// frames of the backtrace outside of anyhow. Numbers in messages and function
+/// names, such as ids, sizes and addresses, are left out so that errors
+/// differing only in such details get the same fingerprint. A number is a
+/// word made of decimal digits, or of hexadecimal ones after `0x`; digits
+/// within a word such as `2fa` are kept.
+///
+/// The hash function is fixed, so fingerprints can be compared across
+/// processes running the same build of a program. An error rebuilt as a
+/// `RemoteError` has the same fingerprint as the error it came from. Across
+/// releases they only stay the same if the error messages and type names
+/// involved do not change, and type names are not guaranteed to be stable
+/// from one version of the compiler to the next. Leave type names out with
+/// [`FingerprintBuilder::type_names`] for fingerprints that survive a
+/// toolchain upgrade.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::{anyhow, Fingerprint};
+///
+/// let a = anyhow!("request 1193 timed out after 30 seconds");
+/// let b = anyhow!("request 2047 timed out after 45 seconds");
+/// assert_eq!(a.fingerprint(), b.fingerprint());
+///
+/// let c = anyhow!("connection refused");
+/// assert_ne!(a.fingerprint(), c.fingerprint());
+///
+/// // Also tell apart errors created from different places in the code.
+/// let fingerprint = Fingerprint::builder().frames(5).fingerprint(&a);
+/// println!("{}", fingerprint);
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Fingerprint {
+    hash: u64,
+}
+
+/// Configuration for computing a [`Fingerprint`].
+///
+/// By default the type names and messages of all errors in the chain are
+/// used, and no backtrace frames.
+#[derive(Clone, Debug)]
+pub struct FingerprintBuilder {
+    type_names: bool,
+    messages: bool,
+    frames: usize,
+}
+
+/// The error returned by [`set_hook`] when a hook has already been installed.
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+#[derive(Debug)]
+pub struct SetHookError {
+    _private: (),
+}
+
+/// Future returned by [`FutureExt::context`].
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct FutureContext<Fut, C> {
+    future: Fut,
+    context: Option<C>,
+    location: Option<&'static crate::location::Location>,
+}
+
+/// Future returned by [`FutureExt::with_context`].
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct FutureWithContext<Fut, F> {
+    future: Fut,
+    context: Option<F>,
+    location: Option<&'static crate::location::Location>,
+}
+
+/// Future returned by [`FutureExt::in_error_scope`].
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct InErrorScope<Fut> {
+    future: Fut,
+    scope: crate::scope::Scope,
+}
+
+/// Iterator or stream returned by `context_each`.
+///
+/// See [`IteratorExt::context_each`] and, with the `futures-core` feature,
+/// `StreamExt::context_each`.
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct ContextEach<I, F> {
+    inner: I,
+    context: F,
+    index: usize,
+    location: Option<&'static crate::location::Location>,
+}
+
+/// Guard returned by [`scope`], which ends the scope when dropped.
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+#[must_use = "the scope ends as soon as the guard is dropped"]
+pub struct ScopeGuard {
+    depth: usize,
+    // Scopes belong to the thread that entered them.
+    _not_send: core::marker::PhantomData<*const ()>,
+}
+
+/// The error returned by [`set_handler`] when a handler has already been
+/// installed.
+#[derive(Debug)]
+pub struct SetHandlerError {
+    _private: (),
+}
+
+/// How serious an error is, for alerting and log levels.
+///
+/// A severity is set on an error with [`Error::with_severity`] or
+/// [`Context::context_severity`], and read back with [`Error::severity`].
+/// Severities are ordered from `Info` to `Critical`, and the severity of an
+/// error is the highest one set on any layer of its chain, so that marking a
+/// low-level failure as critical is not undone by context added on top of it.
+///
+/// The Debug representation of an error shows its severity in front of the
+/// first line, as in `[critical] failed to charge card`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Expected in normal operation, such as a client sending invalid input.
+    Info,
+    /// Worth looking into, but not urgently.
+    Warning,
+    /// A failure that needs fixing.
+    Error,
+    /// A failure that needs attention right away, such as one that pages.
+    Critical,
+}
+
+/// The report handler that renders errors in anyhow's built-in Debug
+/// representation.
+///
+/// This is the handler in effect when none has been installed. Custom handlers
+/// can defer to it for the parts of the report that they do not change.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct DefaultHandler;
+
+/// Iterator of the source locations at which each layer of an error was
+/// created.
+///
+/// This type is the iterator returned by [`Error::locations`]. Locations are
+/// produced outermost first: the place where the most recent context was
+/// attached comes first and the place where the error originated comes last.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::{anyhow, Context, Result};
+///
+/// fn open() -> Result<()> {
+///     Err(anyhow!("no such file or directory"))
+/// }
+///
+/// let error = open().context("failed to load config").unwrap_err();
+/// for location in error.locations() {
+///     eprintln!("at {}", location);
+/// }
+/// ```
+#[cfg(not(anyhow_no_track_caller))]
+pub struct Locations<'a> {
+    layers: crate::error::Layers<'a>,
+}
+
+/// A captured stack backtrace, for the "backtrace" feature.
+///
+/// With the crate's "backtrace" feature enabled, errors capture this type of
+/// backtrace using the `backtrace` crate, which gives access to its individual
+/// [frames][Backtrace::frames]. On Rust 1.65 and newer it is captured along
+/// with the `std::backtrace::Backtrace` of the error and is available through
+/// [`Error::frames_backtrace`]. On older compilers it is the backtrace of the
+/// error, returned by `Error::backtrace`.
+///
+/// Its Display representation is the short form of the backtrace, as in the
+/// Debug representation of an error, unless full backtraces are asked for
+/// with `RUST_BACKTRACE=full`. The alternate form `{:#}` prints every frame.
+///
+/// Backtraces with the same frames, such as those of errors created over and
+/// over at the same place, share their resolved symbols, and have the same
+/// [id][Backtrace::id].
+#[cfg(feature = "backtrace")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "backtrace")))]
+pub struct Backtrace {
+    inner: crate::backtrace::Inner,
+}
+
+/// Identifies the backtraces with the same frames, as returned by
+/// [`Backtrace::id`].
+///
+/// Its Display representation is a 16-digit hexadecimal number.
+#[cfg(feature = "backtrace")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "backtrace")))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BacktraceId {
+    id: u64,
+}
+
+/// Iterator of the frames of an error's backtrace.
+///
+/// This type is the iterator returned by [`Error::frames`]. Frames are produced
+/// innermost first, starting from the place where the error was created.
+#[cfg(feature = "backtrace")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "backtrace")))]
+#[derive(Clone)]
+pub struct Frames<'a> {
+    inner: core::slice::Iter<'a, BacktraceFrame>,
+}
+
+/// A stack frame of a backtrace captured with the "backtrace" feature.
+///
+/// Each frame corresponds to one function call in progress. Its
+/// [symbols][BacktraceFrame::symbols] name the function that the frame is
+/// executing, and any functions that were inlined into it.
+#[cfg(feature = "backtrace")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "backtrace")))]
+pub struct BacktraceFrame {
+    frame: ::backtrace::Frame,
+    symbols: Vec<BacktraceSymbol>,
+}
+
+/// A function, and its place in the source code, that a [`BacktraceFrame`] is
+/// executing.
+#[cfg(feature = "backtrace")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "backtrace")))]
+pub struct BacktraceSymbol {
+    name: Option<Vec<u8>>,
+    filename: Option<crate::backtrace::BytesOrWide>,
+    lineno: Option<u32>,
+    colno: Option<u32>,
+}
+
+/// A backtrace whose frames have not been resolved to functions and source
+/// locations, in a form that can be symbolicated on another machine.
+///
+/// Release binaries are often stripped of their debug information, which is
+/// kept on a build server instead. This type, returned by
+/// [`Error::unresolved_backtrace`], identifies each frame by the
+/// [module][Module] that its code belongs to, an executable or a shared
+/// library, and by the address of the code in the module's ELF file, the one
+/// that its symbol table and debug information refer to. Neither depends on
+/// where the module was loaded in memory.
+///
+/// Its Display representation is a compact text form, which can be sent along
+/// with the error and parsed back with [`str::parse`]. The frames are then
+/// resolved with [`symbolize`][UnresolvedBacktrace::symbolize], using the
+/// debug information of each module.
+///
+/// Modules are only known on Linux. Elsewhere, frames are identified by their
+/// address in memory.
+#[cfg(feature = "backtrace")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "backtrace")))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnresolvedBacktrace {
+    modules: Vec<Module>,
+    frames: Vec<UnresolvedFrame>,
+}
+
+/// An executable or shared library that the frames of an
+/// [`UnresolvedBacktrace`] point into.
+#[cfg(feature = "backtrace")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "backtrace")))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Module {
+    path: String,
+    build_id: Option<Vec<u8>>,
+}
+
+/// A frame of an [`UnresolvedBacktrace`].
+#[cfg(feature = "backtrace")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "backtrace")))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UnresolvedFrame {
+    module: Option<usize>,
+    address: u64,
 }
 
 /// `Result<T, Error>`
@@ -607,6 +1102,287 @@ pub trait Context<T, E>: context::private::Sealed {
     where
         C: Display + Send + Sync + 'static,
         F: FnOnce() -> C;
+
+    /// Wrap the error value with a key-value pair of context.
+    ///
+    /// See [`Error::with_field`]. On an `Option`, the error for `None` is
+    /// made of the field alone.
+    #[cfg(feature = "std")]
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+    fn context_kv<V>(self, key: &'static str, value: V) -> Result<T, Error>
+    where
+        V: Display + Send + Sync + 'static;
+
+    /// Wrap the error value with additional context, and set its severity.
+    ///
+    /// See [`Error::with_severity`].
+    fn context_severity<C>(self, severity: Severity, context: C) -> Result<T, Error>
+    where
+        C: Display + Send + Sync + 'static;
+}
+
+/// Provides `context` methods for futures, and task-local scopes of context.
+///
+/// This trait is sealed and cannot be implemented for types outside of
+/// `anyhow`.
+///
+/// [`context`][FutureExt::context] and
+/// [`with_context`][FutureExt::with_context] are the counterparts of the
+/// methods of the [`Context`] trait for a future whose output is a `Result`:
+/// the error it resolves to, if any, is wrapped in a layer of context located
+/// where the method was called.
+///
+/// [`in_error_scope`][FutureExt::in_error_scope] is the counterpart of
+/// [`scope`] for async code. A thread-local scope entered in an async task
+/// would be left behind on one thread when the task moves to another at an
+/// `.await`, and would leak into unrelated tasks polled on the first one in
+/// the meantime. Instead, the scope of `in_error_scope` is entered each time
+/// the future is polled, on whichever thread polls it, and left again before
+/// the poll returns. Every `anyhow::Error` created or given context while the
+/// future runs is wrapped in a layer of context with its message, which stays
+/// the outermost one.
+///
+/// # Example
+///
+/// ```
+/// # use futures::FutureExt as _;
+/// #
+/// use anyhow::{bail, FutureExt, Result};
+///
+/// async fn charge(amount: u64) -> Result<()> {
+///     if amount == 0 {
+///         bail!("nothing to charge");
+///     }
+///     Ok(())
+/// }
+///
+/// async fn process(id: u64, amount: u64) -> Result<()> {
+///     charge(amount).context("failed to charge").await?;
+///     Ok(())
+/// }
+///
+/// let task = process(7, 0).in_error_scope(|| format!("processing order {}", 7));
+/// # let error = task.now_or_never().unwrap().unwrap_err();
+/// # const IGNORE: &str = stringify! {
+/// let error = executor::block_on(task).unwrap_err();
+/// # };
+/// assert_eq!(
+///     format!("{:#}", error),
+///     "processing order 7: failed to charge: nothing to charge",
+/// );
+/// ```
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub trait FutureExt: core::future::Future + future::private::Sealed + Sized {
+    /// Wrap the error the future resolves to with additional context.
+    fn context<C>(self, context: C) -> FutureContext<Self, C>
+    where
+        C: Display + Send + Sync + 'static;
+
+    /// Wrap the error the future resolves to with additional context that is
+    /// evaluated lazily only once an error does occur.
+    fn with_context<C, F>(self, f: F) -> FutureWithContext<Self, F>
+    where
+        C: Display + Send + Sync + 'static,
+        F: FnOnce() -> C;
+
+    /// Add context to every error created or propagated while the future is
+    /// polled.
+    ///
+    /// The closure is called once, right away.
+    fn in_error_scope<C, F>(self, context: F) -> InErrorScope<Self>
+    where
+        C: Display,
+        F: FnOnce() -> C;
+}
+
+/// Provides the `context_each` method for iterators of `Result`.
+///
+/// This trait is sealed and cannot be implemented for types outside of
+/// `anyhow`.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::{IteratorExt, Result};
+///
+/// let lines = ["1", "2", "three", "4"];
+/// let numbers: Result<Vec<u32>> = lines
+///     .iter()
+///     .map(|line| line.parse::<u32>())
+///     .context_each(|index| format!("invalid number on line {}", index + 1))
+///     .collect();
+///
+/// let error = numbers.unwrap_err();
+/// assert_eq!(
+///     format!("{:#}", error),
+///     "invalid number on line 3: invalid digit found in string",
+/// );
+/// ```
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub trait IteratorExt: Iterator + iter::private::Sealed + Sized {
+    /// Wrap the error of each failed item with additional context, given the
+    /// index of the item.
+    ///
+    /// The index counts all items from the start of the iterator, those that
+    /// succeeded as well as those that failed. The closure is only called for
+    /// the items that failed.
+    fn context_each<C, F>(self, context: F) -> ContextEach<Self, F>
+    where
+        C: Display + Send + Sync + 'static,
+        F: FnMut(usize) -> C;
+
+    /// Collect the successes, or every one of the errors.
+    ///
+    /// This is [`try_collect_all`] as a method.
+    fn try_collect_all<B, T, E>(self) -> Result<B, Error>
+    where
+        Self: Iterator<Item = Result<T, E>>,
+        E: context::ext::StdError + Send + Sync + 'static,
+        B: core::iter::FromIterator<T>;
+}
+
+/// Collects an iterator of `Result`, keeping every error rather than only the
+/// first.
+///
+/// Collecting into a `Result<Vec<T>, E>` stops at the first error and drops
+/// the rest of the iterator. This function instead drives the iterator to the
+/// end. If every item succeeded, it returns the collection of all of them.
+/// Otherwise it returns one [aggregate][Error::aggregate] error holding every
+/// failure, each wrapped in a layer of context with its index in the form
+/// "item N". It displays as "N errors occurred", and its Debug representation
+/// lists each of the failures with its own chain of causes.
+///
+/// The same is available as a method through [`IteratorExt`].
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+///
+/// let lines = ["1", "two", "3", "four"];
+/// let numbers: Result<Vec<u32>> = anyhow::try_collect_all(lines.iter().map(|line| line.parse::<u32>()));
+///
+/// let error = numbers.unwrap_err();
+/// assert_eq!(error.to_string(), "2 errors occurred");
+/// assert_eq!(
+///     format!("{:#}", error.children()[1]),
+///     "item 3: invalid digit found in string",
+/// );
+/// ```
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+#[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+pub fn try_collect_all<B, I, T, E>(iter: I) -> Result<B, Error>
+where
+    I: IntoIterator<Item = Result<T, E>>,
+    E: context::ext::StdError + Send + Sync + 'static,
+    B: core::iter::FromIterator<T>,
+{
+    iter.into_iter().try_collect_all()
+}
+
+/// Provides the `context_each` method for streams of `Result`.
+///
+/// This trait is sealed and cannot be implemented for types outside of
+/// `anyhow`. It is the counterpart of [`IteratorExt`] for a
+/// [`Stream`][futures_core::Stream].
+///
+/// # Example
+///
+/// ```
+/// use anyhow::{Result, StreamExt as _};
+/// use futures::stream::{self, StreamExt as _, TryStreamExt};
+///
+/// # futures::FutureExt::now_or_never(async {
+/// let lines = stream::iter(vec!["1", "2", "three", "4"]);
+/// let numbers: Result<Vec<u32>> = lines
+///     .map(|line| line.parse::<u32>())
+///     .context_each(|index| format!("invalid number on line {}", index + 1))
+///     .try_collect()
+///     .await;
+///
+/// let error = numbers.unwrap_err();
+/// assert_eq!(
+///     format!("{:#}", error),
+///     "invalid number on line 3: invalid digit found in string",
+/// );
+/// # }).unwrap();
+/// ```
+#[cfg(all(feature = "std", feature = "futures-core"))]
+#[cfg_attr(doc_cfg, doc(cfg(all(feature = "std", feature = "futures-core"))))]
+pub trait StreamExt: futures_core::Stream + iter::private::SealedStream + Sized {
+    /// Wrap the error of each failed item with additional context, given the
+    /// index of the item.
+    ///
+    /// The index counts all items from the start of the stream, those that
+    /// succeeded as well as those that failed. The closure is only called for
+    /// the items that failed.
+    fn context_each<C, F>(self, context: F) -> ContextEach<Self, F>
+    where
+        C: Display + Send + Sync + 'static,
+        F: FnMut(usize) -> C;
+}
+
+/// Renders an [`Error`] for its Debug representation.
+///
+/// The Debug representation of an error is what gets printed when an error is
+/// returned from `fn main`, or when it is formatted with `{:?}`. By default it
+/// shows the error, its [chain][Error::chain] of causes under "Caused by:",
+/// and its backtrace if one was captured. A different
+/// layout, for example a terse one for a command line tool and a single-line
+/// one for a daemon's logs, can be had by implementing `ReportHandler` and
+/// installing it for the whole program with [`set_handler`], or for one error
+/// with [`Error::with_handler`].
+///
+/// The handler receives the outermost error, from which it has access to the
+/// chain of causes, the backtrace, the source locations and any values
+/// attached with [`Error::attach`], through
+/// [`request_ref`][Error::request_ref]. It also receives the formatter, so it
+/// can honor flags such as `{:#?}`; [`DefaultHandler`] renders that one as a
+/// conventional struct-style Debug representation.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::{anyhow, Context, Error, ReportHandler};
+/// use std::fmt;
+///
+/// struct OneLine;
+///
+/// impl ReportHandler for OneLine {
+///     fn debug(&self, error: &Error, f: &mut fmt::Formatter) -> fmt::Result {
+///         write!(f, "error: {:#}", error)
+///     }
+/// }
+///
+/// let error = anyhow!("connection refused")
+///     .context("failed to run query")
+///     .with_handler(OneLine);
+/// assert_eq!(
+///     format!("{:?}", error),
+///     "error: failed to run query: connection refused",
+/// );
+/// ```
+pub trait ReportHandler: Send + Sync + 'static {
+    /// Formats the error for `{:?}`.
+    fn debug(&self, error: &Error, f: &mut core::fmt::Formatter) -> core::fmt::Result;
+}
+
+/// Installs the [`ReportHandler`] used to render every error with `{:?}`.
+///
+/// Errors that were given their own handler with [`Error::with_handler`]
+/// keep using that one.
+///
+/// A handler can only be installed once per process, typically at the start of
+/// `main`. Any call after the first returns an error and leaves the first
+/// handler in place.
+pub fn set_handler<H>(handler: H) -> Result<(), SetHandlerError>
+where
+    H: ReportHandler,
+{
+    crate::report::set(alloc::boxed::Box::new(handler))
 }
 
 /// Equivalent to Ok::<_, anyhow::Error>(value).
@@ -632,6 +1408,320 @@ pub fn Ok<T>(t: T) -> Result<T> {
     Result::Ok(t)
 }
 
+/// Invokes a closure, turning a panic inside of it into an error.
+///
+/// This is [`std::panic::catch_unwind`] for closures that return an
+/// [`anyhow::Result`][Result]. Whether the closure returns an error or panics,
+/// the outcome is an `Err` holding an `anyhow::Error`, so the two can be
+/// handled the same way. A panic becomes a [`PanicError`] as by
+/// [`Error::from_panic`], with the location of the panic and the backtrace
+/// captured when it was raised.
+///
+/// To record the location and backtrace, the first call installs a panic hook
+/// with `std::panic::set_hook` that defers to the previously installed hook,
+/// which still prints the panic message as usual. A panic hook installed
+/// afterwards replaces it, in which case errors only carry the panic message.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::{bail, Result};
+///
+/// fn run(job: u32) -> Result<u32> {
+///     match job {
+///         0 => bail!("nothing to do"),
+///         1 => panic!("job {} crashed", job),
+///         _ => Ok(job * 2),
+///     }
+/// }
+///
+/// for job in 0..3 {
+///     match anyhow::catch_unwind(|| run(job)) {
+///         Ok(output) => println!("job {}: {}", job, output),
+///         Err(error) => println!("job {} failed: {:?}", job, error),
+///     }
+/// }
+/// ```
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+#[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+pub fn catch_unwind<F, T>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + std::panic::UnwindSafe,
+{
+    crate::panic::catch_unwind(f)
+}
+
+/// Registers a function to be called whenever an `anyhow::Error` is created.
+///
+/// The hook runs once for every new error, whether it comes from
+/// [`anyhow!`], [`bail!`], [`ensure!`], a `?` conversion or [`Error::new`],
+/// and once for every layer of context added with [`Error::context`] or the
+/// [`Context`] trait. It receives the error as it was just created, and can
+/// inspect it, for example to count errors by the type of their
+/// [root cause][Error::root_cause] or to sample them into a log. The layers
+/// added by a [scope][scope()] are part of the error being created and do not
+/// run the hook again.
+///
+/// Only one hook can be installed, once per process, typically at the start
+/// of `main`. Unlike `std::panic::set_hook`, a hook cannot be replaced: any
+/// call after the first returns an error and leaves the first hook in place.
+///
+/// Errors created from within the hook, on the same thread, do not invoke the
+/// hook again.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::anyhow;
+/// use std::io;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// static ERRORS: AtomicUsize = AtomicUsize::new(0);
+/// static IO_ERRORS: AtomicUsize = AtomicUsize::new(0);
+///
+/// fn hook(error: &anyhow::Error) {
+///     ERRORS.fetch_add(1, Ordering::Relaxed);
+///     if error.root_cause().is::<io::Error>() {
+///         IO_ERRORS.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// anyhow::set_hook(hook).unwrap();
+/// assert!(anyhow::set_hook(hook).is_err());
+///
+/// let _ = anyhow!("connection refused").context("failed to run query");
+/// assert_eq!(ERRORS.load(Ordering::Relaxed), 2);
+/// assert_eq!(IO_ERRORS.load(Ordering::Relaxed), 0);
+/// ```
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub fn set_hook(hook: fn(&Error)) -> Result<(), SetHookError> {
+    crate::hook::set(hook)
+}
+
+/// Adds context to every error created or propagated on this thread until the
+/// guard is dropped.
+///
+/// Any `anyhow::Error` created while the returned guard is alive, including
+/// one converted from another error type by the `?` operator, is wrapped in a
+/// layer of context with the given message, just as if [`Error::context`] had
+/// been called on it. So is an `anyhow::Error` created elsewhere, such as
+/// before the scope was entered or on another thread, once it is propagated
+/// through the scope with context added to it by [`Error::context`],
+/// [`Error::with_field`] or the [`Context`] trait. The layer shows up in the
+/// `{:#}` and `{:?}` representations of the error like any other, at the
+/// location where the scope was entered. This saves threading the same
+/// `.context(...)` through every `?` in a block of code.
+///
+/// The layer of the scope stays the outermost one while the scope lasts:
+/// context added to the error inside the scope goes beneath it, and context
+/// added after the scope has ended goes outside of it. Scopes nest: an error
+/// gets one layer for each, with the outermost scope as the outermost layer.
+///
+/// The closure is called when the first error needs the message, if any, and
+/// at most once. It must be `'static`, so a closure that formats local values
+/// takes them with `move`.
+///
+/// An `anyhow::Error` that passes through the scope by `?` alone, in a
+/// function returning `anyhow::Result`, does not get the layer of the scope:
+/// that `?` is an identity conversion, which anyhow does not take part in.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::{bail, Context, Result};
+///
+/// fn charge(amount: u64) -> Result<()> {
+///     if amount == 0 {
+///         bail!("nothing to charge");
+///     }
+///     Ok(())
+/// }
+///
+/// fn process(id: u64, amount: u64) -> Result<()> {
+///     let _scope = anyhow::scope(move || format!("processing order {}", id));
+///     charge(amount).context("failed to charge")?;
+///     Ok(())
+/// }
+///
+/// let error = process(7, 0).unwrap_err();
+/// assert_eq!(
+///     format!("{:#}", error),
+///     "processing order 7: failed to charge: nothing to charge",
+/// );
+/// ```
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+#[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+pub fn scope<C, F>(context: F) -> ScopeGuard
+where
+    C: Display,
+    F: FnOnce() -> C + 'static,
+{
+    crate::scope::enter_lazy(context)
+}
+
+/// Shows the source location of each layer of an error in its Debug
+/// representation.
+///
+/// This is off by default. Once turned on, the Debug format "{:?}" shows below
+/// the error the source location at which it was created, and below each of
+/// its causes the location at which that layer was created or had context
+/// added, as `at file:line:column`. Causes that did not go through anyhow,
+/// such as the `std::io::Error` below, have no location to show.
+///
+/// ```console
+/// Error: Failed to read instrs from ./path/to/instrs.json
+///     at src/main.rs:7:10
+///
+/// Caused by:
+///     No such file or directory (os error 2)
+/// ```
+///
+/// The locations are recorded either way, and
+/// [`Error::locations`] returns them.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::anyhow;
+///
+/// anyhow::set_debug_locations(true);
+/// let error = anyhow!("oh no!");
+/// assert!(format!("{:?}", error).starts_with("oh no!\n    at "));
+/// ```
+pub fn set_debug_locations(show: bool) {
+    crate::location::set_shown(show);
+}
+
+/// Folds the frames of the given crates in the backtraces of errors.
+///
+/// Unless full backtraces are asked for with `RUST_BACKTRACE=full` or
+/// `RUST_LIB_BACKTRACE=full`, the backtrace in the Debug representation of an
+/// error is printed in a short form. It starts at the frame where the error
+/// was created, without the frames of anyhow and of the standard library
+/// leading up to it, and ends at `main` or at the closure that a thread was
+/// spawned with. On top of that, each run of consecutive frames from one of the
+/// crates given here, such as the internals of an async runtime, is shown as a
+/// single line.
+///
+/// Entries are crate names like `"tokio"`, or paths within a crate like
+/// `"tokio::runtime"`. Each call replaces the list given to the previous one.
+///
+/// # Example
+///
+/// ```
+/// anyhow::set_collapsed_crates(&["tokio", "hyper"]);
+/// ```
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub fn set_collapsed_crates(crates: &'static [&'static str]) {
+    crate::frames::set_collapsed(crates);
+}
+
+/// Shows where each layer of context was added with a short backtrace.
+///
+/// With [`set_debug_locations`], the Debug representation of an error shows
+/// the source location at which each layer of context was added. With
+/// `set_context_backtraces(n)`, adding context with [`Error::context`] or the
+/// [`Context`] trait also captures a backtrace, and the first `n` frames of its
+/// short form are shown below that layer. This helps where the same function
+/// adds context on behalf of many callers.
+///
+/// A backtrace is captured for every layer, regardless of the environment
+/// variables and the [backtrace policy][set_backtrace_policy]. With the
+/// "backtrace" feature, capturing it only walks the innermost frames of the
+/// stack, enough for the `n` frames shown and the frames of anyhow and of the
+/// standard library above them. Otherwise it uses the standard library's
+/// backtrace, which walks the whole stack, and every layer costs as much as
+/// capturing the backtrace of the error itself. Either way the frames are only
+/// resolved to function names when the error is printed. This only applies to
+/// context added from then on. Passing 0, the default, turns it off.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::{anyhow, Context, Result};
+///
+/// fn load() -> Result<()> {
+///     Err(anyhow!("file not found")).context("failed to load config")
+/// }
+///
+/// anyhow::set_debug_locations(true);
+/// anyhow::set_context_backtraces(3);
+/// let error = load().unwrap_err();
+/// println!("{:?}", error);
+/// ```
+///
+/// ```console
+/// failed to load config
+///     at src/main.rs:4:36
+///        0: main::load
+///                  at ./src/main.rs:4:36
+///        1: main::main
+///                  at ./src/main.rs:9:17
+///
+/// Caused by:
+///     file not found
+///     at src/main.rs:4:9
+/// ```
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub fn set_context_backtraces(frames: usize) {
+    crate::context::set_layer_frames(frames);
+}
+
+/// Which new errors capture a backtrace, as set by [`set_backtrace_policy`].
+///
+/// Capturing a backtrace walks the stack of the current thread, which costs
+/// far more than creating the error itself. A program that creates many
+/// errors and handles them right away, for example while retrying or parsing
+/// untrusted input, can keep backtraces enabled for the errors that matter by
+/// choosing a policy that captures fewer of them.
+///
+/// Backtraces are still only captured if they are enabled through the
+/// environment variables described in the [crate documentation][crate].
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BacktracePolicy {
+    /// Capture a backtrace for every error. This is the default.
+    Always,
+    /// Capture no backtraces.
+    Never,
+    /// Capture backtraces for at most this many errors per second, across
+    /// all threads.
+    Sampled(u32),
+    /// Capture a backtrace for the first error created at each place in the
+    /// source code, such as each `bail!` or each `?` converting some other
+    /// error type. On compilers older than Rust 1.46, which cannot tell those
+    /// places apart, this is the same as `Always`.
+    OncePerCallSite,
+}
+
+/// Sets which new errors capture a backtrace.
+///
+/// The policy applies to errors created from then on, by any thread. Errors
+/// that do not capture a backtrace print without one, and their
+/// [`backtrace()`][Error::backtrace] reports that it is disabled.
+///
+/// To create an individual error without a backtrace regardless of the policy,
+/// use [`Error::new_without_backtrace`] or [`Error::msg_without_backtrace`].
+///
+/// # Example
+///
+/// ```
+/// use anyhow::BacktracePolicy;
+///
+/// anyhow::set_backtrace_policy(BacktracePolicy::Sampled(10));
+/// ```
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub fn set_backtrace_policy(policy: BacktracePolicy) {
+    crate::backtrace::set_policy(policy);
+}
+
 // Not public API. Referenced by macro-generated code.
 #[doc(hidden)]
 pub mod __private {
@@ -655,6 +1745,7 @@ pub mod __private {
     #[doc(hidden)]
     #[inline]
     #[cold]
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
     pub fn format_err(args: Arguments) -> Error {
         #[cfg(anyhow_no_fmt_arguments_as_str)]
         let fmt_arguments_as_str = None::<&str>;
@@ -0,0 +1,55 @@
+#[cfg(not(anyhow_no_track_caller))]
+use crate::error::ErrorImpl;
+#[cfg(not(anyhow_no_track_caller))]
+use crate::Locations;
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+// Whether the Debug representation shows the locations, which it does not by
+// default so as to keep the output that programs may be matching on.
+static SHOWN: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn set_shown(shown: bool) {
+    SHOWN.store(shown, Ordering::Relaxed);
+}
+
+pub(crate) fn shown() -> bool {
+    SHOWN.load(Ordering::Relaxed)
+}
+
+#[cfg(not(anyhow_no_track_caller))]
+pub(crate) type Location = core::panic::Location<'static>;
+
+#[cfg(anyhow_no_track_caller)]
+pub(crate) enum Location {}
+
+#[cfg(anyhow_no_track_caller)]
+impl core::fmt::Display for Location {
+    fn fmt(&self, _formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match *self {}
+    }
+}
+
+#[cfg(not(anyhow_no_track_caller))]
+macro_rules! location {
+    () => {
+        Some(core::panic::Location::caller())
+    };
+}
+
+#[cfg(anyhow_no_track_caller)]
+macro_rules! location {
+    () => {
+        None
+    };
+}
+
+#[cfg(not(anyhow_no_track_caller))]
+impl<'a> Iterator for Locations<'a> {
+    type Item = &'static Location;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.layers
+            .find_map(|layer| unsafe { ErrorImpl::location(layer) })
+    }
+}
@@ -0,0 +1,158 @@
+use crate::backtrace::Captured;
+use crate::{Error, PanicError, Result, StdError};
+use core::any::Any;
+use core::fmt::{self, Debug, Display};
+use std::cell::{Cell, RefCell};
+use std::panic::{self, UnwindSafe};
+use std::sync::Once;
+use std::thread;
+
+thread_local! {
+    // Number of calls to anyhow::catch_unwind in progress on this thread. The
+    // panic hook only records panics that one of them is about to catch.
+    static DEPTH: Cell<usize> = Cell::new(0);
+
+    // The most recent panic recorded by the panic hook on this thread.
+    static RECORD: RefCell<Option<Record>> = RefCell::new(None);
+}
+
+struct Record {
+    message: String,
+    location: Option<String>,
+    backtrace: Option<Captured>,
+}
+
+impl PanicError {
+    /// The message that the panic was raised with.
+    ///
+    /// Panics raised with something other than a string, for example through
+    /// `std::panic::panic_any`, have the message `Box<dyn Any>`.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The source location of the panic, formatted as `file:line:column`.
+    ///
+    /// This is only known for panics caught by
+    /// [`catch_unwind`][crate::catch_unwind].
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_ref().map(String::as_str)
+    }
+}
+
+impl Display for PanicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Debug for PanicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PanicError")
+            .field("message", &self.message)
+            .field("location", &self.location)
+            .finish()
+    }
+}
+
+impl StdError for PanicError {}
+
+// Builds the error for a panic payload, together with the backtrace captured
+// when the panic was raised, if it was recorded.
+pub(crate) fn error(payload: &(dyn Any + Send)) -> (PanicError, Option<Captured>) {
+    let message = message(payload);
+    let record = RECORD
+        .try_with(|record| record.borrow_mut().take())
+        .unwrap_or(None);
+    match record {
+        // The payload may come from a panic on another thread, or from one
+        // that this thread caught without anyhow; only use the record if it
+        // is for the same panic.
+        Some(record) if record.message == message => (
+            PanicError {
+                message,
+                location: record.location,
+            },
+            record.backtrace,
+        ),
+        _ => (
+            PanicError {
+                message,
+                location: None,
+            },
+            None,
+        ),
+    }
+}
+
+fn message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&'static str>() {
+        (*message).to_owned()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_owned()
+    }
+}
+
+// Installs a panic hook that records the location and backtrace of panics
+// raised inside of catch_unwind, and then calls the previously installed hook.
+fn install_hook() {
+    static INSTALL: Once = Once::new();
+
+    // The panic hook cannot be changed while panicking.
+    if thread::panicking() {
+        return;
+    }
+
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let armed = DEPTH.try_with(|depth| depth.get() > 0).unwrap_or(false);
+            if armed {
+                let record = Record {
+                    message: message(info.payload()),
+                    location: info.location().map(ToString::to_string),
+                    backtrace: backtrace!(),
+                };
+                let _ = RECORD.try_with(|slot| *slot.borrow_mut() = Some(record));
+            }
+            previous(info);
+        }));
+    });
+}
+
+#[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+pub(crate) fn catch_unwind<F, T>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + UnwindSafe,
+{
+    struct Armed;
+
+    impl Armed {
+        fn new() -> Self {
+            DEPTH.with(|depth| depth.set(depth.get() + 1));
+            Armed
+        }
+    }
+
+    impl Drop for Armed {
+        fn drop(&mut self) {
+            let _ = DEPTH.try_with(|depth| depth.set(depth.get() - 1));
+        }
+    }
+
+    install_hook();
+    let armed = Armed::new();
+    let result = panic::catch_unwind(f);
+    drop(armed);
+
+    match result {
+        Ok(result) => {
+            // Forget any panic that was caught inside of f by other means.
+            let _ = RECORD.try_with(|record| record.borrow_mut().take());
+            result
+        }
+        Err(payload) => Err(Error::from_panic(payload)),
+    }
+}
@@ -0,0 +1,323 @@
+use crate::chain::Chain;
+use crate::{RemoteError, StdError};
+use core::fmt::{self, Debug, Display, Write};
+use serde::de::{self, Deserialize, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+
+impl RemoteError {
+    /// The Rust type name of the original error at this position in the
+    /// chain, if it was known when the error was serialized.
+    pub fn type_name(&self) -> Option<&str> {
+        self.type_name.as_ref().map(String::as_str)
+    }
+
+    /// Returns true if the original error at this position in the chain was
+    /// of type `T`.
+    ///
+    /// This compares type names, which are only as precise as
+    /// [`core::any::type_name`].
+    pub fn is<T>(&self) -> bool
+    where
+        T: ?Sized,
+    {
+        self.type_name() == Some(core::any::type_name::<T>())
+    }
+
+    /// The source location at which the original error at this position in
+    /// the chain was created, formatted as `file:line:column`.
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_ref().map(String::as_str)
+    }
+
+    /// The backtrace captured by the original error, as text.
+    ///
+    /// Only the outermost `RemoteError` of a chain carries a backtrace.
+    pub fn backtrace(&self) -> Option<&str> {
+        self.backtrace.as_ref().map(String::as_str)
+    }
+}
+
+// The backtrace of the first RemoteError in the chain that has one.
+pub(crate) fn backtrace<'a>(error: &'a (dyn StdError + 'static)) -> Option<&'a str> {
+    Chain::new(error)
+        .filter_map(|error| error.downcast_ref::<RemoteError>())
+        .find_map(RemoteError::backtrace)
+}
+
+impl Display for RemoteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Debug for RemoteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RemoteError")
+            .field("message", &self.message)
+            .field("type_name", &self.type_name)
+            .field("location", &self.location)
+            .field("source", &self.source)
+            .finish()
+    }
+}
+
+impl StdError for RemoteError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match &self.source {
+            Some(source) => Some(&**source),
+            None => None,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for RemoteError {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(ErrorVisitor)
+    }
+}
+
+// The outermost error. Its causes arrive as a flat list, outermost first, and
+// are linked up through `source` from the innermost one.
+struct ErrorVisitor;
+
+impl<'de> Visitor<'de> for ErrorVisitor {
+    type Value = RemoteError;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a serialized anyhow::Error")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut fields = Fields::default();
+        let mut causes: Vec<Cause> = Vec::new();
+        let mut backtrace = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "causes" => causes = map.next_value()?,
+                "backtrace" => backtrace = map.next_value::<Backtrace>()?.0,
+                _ => fields.visit(key, &mut map)?,
+            }
+        }
+
+        let mut source = None;
+        for cause in causes.into_iter().rev() {
+            let mut error = cause.0.finish::<A::Error>()?;
+            error.source = source.map(Box::new);
+            source = Some(error);
+        }
+
+        let mut error = fields.finish::<A::Error>()?;
+        error.backtrace = backtrace;
+        error.source = source.map(Box::new);
+        Ok(error)
+    }
+}
+
+#[derive(Default)]
+struct Fields {
+    message: Option<String>,
+    type_name: Option<String>,
+    location: Option<String>,
+}
+
+impl Fields {
+    fn visit<'de, A>(&mut self, key: String, map: &mut A) -> Result<(), A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        match key.as_str() {
+            "message" => self.message = Some(map.next_value()?),
+            "type_name" => self.type_name = map.next_value()?,
+            "location" => self.location = map.next_value()?,
+            _ => {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish<E>(self) -> Result<RemoteError, E>
+    where
+        E: de::Error,
+    {
+        Ok(RemoteError {
+            message: self.message.ok_or_else(|| E::missing_field("message"))?,
+            type_name: self.type_name,
+            location: self.location,
+            backtrace: None,
+            source: None,
+        })
+    }
+}
+
+struct Cause(Fields);
+
+impl<'de> Deserialize<'de> for Cause {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct CauseVisitor;
+
+        impl<'de> Visitor<'de> for CauseVisitor {
+            type Value = Cause;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a serialized cause")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut fields = Fields::default();
+                while let Some(key) = map.next_key::<String>()? {
+                    fields.visit(key, &mut map)?;
+                }
+                Ok(Cause(fields))
+            }
+        }
+
+        deserializer.deserialize_map(CauseVisitor)
+    }
+}
+
+// Either the text of a backtrace, or its frames as serialized by
+// anyhow::Error, which are rendered back into text the way std does.
+struct Backtrace(Option<String>);
+
+impl<'de> Deserialize<'de> for Backtrace {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct BacktraceVisitor;
+
+        impl<'de> Visitor<'de> for BacktraceVisitor {
+            type Value = Backtrace;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a backtrace as text or as a list of frames")
+            }
+
+            fn visit_unit<E>(self) -> Result<Self::Value, E> {
+                Ok(Backtrace(None))
+            }
+
+            fn visit_none<E>(self) -> Result<Self::Value, E> {
+                Ok(Backtrace(None))
+            }
+
+            fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                deserializer.deserialize_any(self)
+            }
+
+            fn visit_str<E>(self, text: &str) -> Result<Self::Value, E> {
+                Ok(Backtrace(Some(text.to_owned())))
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut text = String::new();
+                let mut previous = None;
+                let mut position = 0;
+                while let Some(frame) = seq.next_element::<Frame>()? {
+                    if position > 0 {
+                        text.push('\n');
+                    }
+                    let index = frame.index.unwrap_or(position);
+                    // Inlined functions share the index of their frame.
+                    let inlined = previous == Some(index);
+                    frame.render(index, inlined, &mut text);
+                    previous = Some(index);
+                    position += 1;
+                }
+                Ok(Backtrace(Some(text)))
+            }
+        }
+
+        deserializer.deserialize_any(BacktraceVisitor)
+    }
+}
+
+#[derive(Default)]
+struct Frame {
+    index: Option<usize>,
+    function: Option<String>,
+    file: Option<String>,
+    line: Option<u32>,
+    column: Option<u32>,
+}
+
+impl Frame {
+    fn render(&self, index: usize, inlined: bool, text: &mut String) {
+        let function = self
+            .function
+            .as_ref()
+            .map(String::as_str)
+            .unwrap_or("<unknown>");
+        if inlined {
+            let _ = write!(text, "      {}", function);
+        } else {
+            let _ = write!(text, "{:>4}: {}", index, function);
+        }
+        if let Some(file) = &self.file {
+            let _ = write!(text, "\n             at {}", file);
+            if let Some(line) = self.line {
+                let _ = write!(text, ":{}", line);
+                if let Some(column) = self.column {
+                    let _ = write!(text, ":{}", column);
+                }
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Frame {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct FrameVisitor;
+
+        impl<'de> Visitor<'de> for FrameVisitor {
+            type Value = Frame;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a backtrace frame")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut frame = Frame::default();
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "index" => frame.index = map.next_value()?,
+                        "function" => frame.function = map.next_value()?,
+                        "file" => frame.file = map.next_value()?,
+                        "line" => frame.line = map.next_value()?,
+                        "column" => frame.column = map.next_value()?,
+                        _ => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+                Ok(frame)
+            }
+        }
+
+        deserializer.deserialize_map(FrameVisitor)
+    }
+}
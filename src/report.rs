@@ -0,0 +1,64 @@
+use crate::error::ErrorImpl;
+use crate::{DefaultHandler, Error, ReportHandler, SetHandlerError};
+use alloc::boxed::Box;
+use core::fmt;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+#[cfg(feature = "std")]
+use crate::StdError;
+
+// The globally installed handler, or null. Once installed it is never freed.
+static HANDLER: AtomicPtr<Box<dyn ReportHandler>> = AtomicPtr::new(ptr::null_mut());
+
+// A handler installed on one particular error. It is stored among the error's
+// attachments so that it stays in effect when context is added.
+pub(crate) struct Handler(pub(crate) Box<dyn ReportHandler>);
+
+pub(crate) fn set(handler: Box<dyn ReportHandler>) -> Result<(), SetHandlerError> {
+    let handler = Box::into_raw(Box::new(handler));
+    match HANDLER.compare_exchange(
+        ptr::null_mut(),
+        handler,
+        Ordering::AcqRel,
+        Ordering::Acquire,
+    ) {
+        Ok(_) => Ok(()),
+        Err(_) => {
+            // Safety: the handler was not published, so this is still the only
+            // pointer to it.
+            drop(unsafe { Box::from_raw(handler) });
+            Err(SetHandlerError { _private: () })
+        }
+    }
+}
+
+pub(crate) fn debug(error: &Error, formatter: &mut fmt::Formatter) -> fmt::Result {
+    if let Some(handler) = error.request_ref::<Handler>() {
+        return handler.0.debug(error, formatter);
+    }
+
+    let handler = HANDLER.load(Ordering::Acquire);
+    if !handler.is_null() {
+        // Safety: an installed handler lives for the rest of the program.
+        let handler = unsafe { &*handler };
+        return handler.debug(error, formatter);
+    }
+
+    DefaultHandler.debug(error, formatter)
+}
+
+impl ReportHandler for DefaultHandler {
+    fn debug(&self, error: &Error, formatter: &mut fmt::Formatter) -> fmt::Result {
+        unsafe { ErrorImpl::debug(error.inner.by_ref(), formatter) }
+    }
+}
+
+impl fmt::Display for SetHandlerError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an anyhow report handler has already been installed")
+    }
+}
+
+#[cfg(feature = "std")]
+impl StdError for SetHandlerError {}
@@ -0,0 +1,217 @@
+use crate::location::Location;
+use crate::{Error, ScopeGuard};
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::fmt::{self, Display};
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::rc::Rc;
+
+// A scope of context as kept by a future, which enters it every time it is
+// polled. Its message is rendered when the scope is created.
+#[derive(Clone)]
+pub(crate) struct Scope {
+    id: usize,
+    message: Arc<str>,
+    location: Option<&'static Location>,
+}
+
+// A scope entered on this thread.
+#[derive(Clone)]
+struct Entry {
+    id: usize,
+    message: Message,
+    location: Option<&'static Location>,
+}
+
+#[derive(Clone)]
+enum Message {
+    Rendered(Arc<str>),
+    Lazy(Rc<Lazy>),
+}
+
+// The message of a scope entered with anyhow::scope, rendered when the first
+// error in the scope needs it.
+struct Lazy {
+    context: Cell<Option<Box<dyn FnOnce() -> String>>>,
+    rendered: RefCell<Option<Arc<str>>>,
+}
+
+// The context of the layer that a scope adds to an error. Errors wrapped in the
+// same scope share its message.
+pub(crate) struct ScopeMessage {
+    id: usize,
+    message: Arc<str>,
+}
+
+thread_local! {
+    // The scopes entered on this thread, outermost first.
+    static SCOPES: RefCell<Vec<Entry>> = RefCell::new(Vec::new());
+}
+
+// Identifies a scope among all those entered in the process, including those
+// of futures polled on more than one thread.
+fn next_id() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+impl Scope {
+    #[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+    pub(crate) fn new<C>(context: C) -> Self
+    where
+        C: Display,
+    {
+        Scope {
+            id: next_id(),
+            message: Arc::from(context.to_string()),
+            location: location!(),
+        }
+    }
+}
+
+impl Message {
+    // None while the message is being rendered, for errors created by the
+    // closure that renders it.
+    fn get(&self) -> Option<Arc<str>> {
+        match self {
+            Message::Rendered(message) => Some(message.clone()),
+            Message::Lazy(lazy) => {
+                if let Some(context) = lazy.context.take() {
+                    let message = Arc::from(context());
+                    *lazy.rendered.borrow_mut() = Some(message);
+                }
+                lazy.rendered.borrow().clone()
+            }
+        }
+    }
+}
+
+impl Display for ScopeMessage {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(&self.message)
+    }
+}
+
+// Enters the scope of a future for one poll.
+pub(crate) fn enter(scope: &Scope) -> ScopeGuard {
+    push(Entry {
+        id: scope.id,
+        message: Message::Rendered(scope.message.clone()),
+        location: scope.location,
+    })
+}
+
+#[cfg_attr(not(anyhow_no_track_caller), track_caller)]
+pub(crate) fn enter_lazy<C, F>(context: F) -> ScopeGuard
+where
+    C: Display,
+    F: FnOnce() -> C + 'static,
+{
+    let lazy = Lazy {
+        context: Cell::new(Some(Box::new(move || context().to_string()))),
+        rendered: RefCell::new(None),
+    };
+    push(Entry {
+        id: next_id(),
+        message: Message::Lazy(Rc::new(lazy)),
+        location: location!(),
+    })
+}
+
+fn push(entry: Entry) -> ScopeGuard {
+    let depth = SCOPES.with(|scopes| {
+        let mut scopes = scopes.borrow_mut();
+        scopes.push(entry);
+        scopes.len() - 1
+    });
+    ScopeGuard {
+        depth,
+        _not_send: PhantomData,
+    }
+}
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        // The thread local is unavailable while the thread is being torn down,
+        // by which point there is nothing left to pop.
+        let depth = self.depth;
+        let _ = SCOPES.try_with(|scopes| scopes.borrow_mut().truncate(depth));
+    }
+}
+
+// Wraps a newly created error in the scopes entered on this thread.
+pub(crate) fn apply(error: Error) -> Error {
+    beneath(error, |error| error)
+}
+
+// Adds to an error beneath the layers of the scopes entered on this thread,
+// which stay outermost, and wraps it in a layer for each of these scopes that
+// it has none for yet, the outermost scope outermost. This is where scopes are
+// applied, both to an error as it is created and to one that has context
+// added in the scopes, which is how an error propagated through them gets
+// their layers.
+pub(crate) fn beneath<F>(error: Error, add: F) -> Error
+where
+    F: FnOnce(Error) -> Error,
+{
+    // Copied out rather than borrowed, as rendering a message or adding to the
+    // error may create errors of their own.
+    let entries = SCOPES
+        .try_with(|scopes| scopes.borrow().clone())
+        .unwrap_or_default();
+    if entries.is_empty() {
+        return add(error);
+    }
+
+    let mut error = error;
+    let mut split = Vec::new();
+    loop {
+        match error.split_scope() {
+            Ok((layer, inner)) => {
+                if entries.iter().any(|entry| entry.id == layer.scope().id) {
+                    split.push(layer);
+                    error = inner;
+                } else {
+                    // Left behind by a scope that has ended.
+                    error = layer.wrap(inner);
+                    break;
+                }
+            }
+            Err(unscoped) => {
+                error = unscoped;
+                break;
+            }
+        }
+    }
+
+    let mut error = add(error);
+    for entry in entries.iter().rev() {
+        if let Some(i) = split.iter().position(|layer| layer.scope().id == entry.id) {
+            error = split.swap_remove(i).wrap(error);
+        } else if !contains(&error, entry.id) {
+            if let Some(message) = entry.message.get() {
+                let context = ScopeMessage {
+                    id: entry.id,
+                    message,
+                };
+                error = error.layer_at(context, entry.location);
+            }
+        }
+    }
+    error
+}
+
+// Whether any layer of the error is that of the given scope, such as for an
+// error created in the scope of a future that is polled again.
+fn contains(error: &Error, id: usize) -> bool {
+    error.chain().any(|error| {
+        match error.downcast_ref::<crate::error::ContextError<ScopeMessage, Error>>() {
+            Some(layer) => layer.context.id == id,
+            None => false,
+        }
+    })
+}
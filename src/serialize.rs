@@ -0,0 +1,283 @@
+use crate::chain::Chain;
+use crate::error::{ErrorImpl, Owned};
+use crate::location::Location;
+use crate::ptr::Ref;
+#[cfg(feature = "backtrace")]
+use crate::BacktraceSymbol;
+use crate::{Error, StdError};
+#[cfg(feature = "std")]
+use crate::{PanicError, RemoteError};
+use alloc::vec::Vec;
+#[cfg(feature = "backtrace")]
+use core::cmp;
+use core::fmt::Display;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+// Schema:
+//
+//     {
+//         "message": "...",
+//         "type_name": "..." | null,
+//         "location": "file:line:column" | null,
+//         "causes": [{ "message": "...", "type_name": "..." | null,
+//                      "location": "file:line:column" | null }, ...],
+//         "backtrace": [{ "index": 0, "function": "..." | null, "file": "..." | null,
+//                         "line": 0 | null, "column": 0 | null }, ...]
+//                      | "..." | null
+//     }
+//
+// The type name is known only for errors owned by a layer of an anyhow::Error,
+// i.e. values passed to Error::new, anyhow!, ? or context and the errors that
+// Context::context was called on, and not for sources found further down
+// through std::error::Error::source. The location is known for the former
+// only. A RemoteError serializes as the error that it was rebuilt from, and a
+// PanicError with the location of the panic.
+//
+// The frames of the backtrace are listed one by one with the "backtrace"
+// feature, which captures them in place of the standard library's backtrace.
+// Otherwise only the text of the backtrace is known, as it is printed, and so
+// is that of a RemoteError.
+
+impl Serialize for Error {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let this = self.inner.by_ref();
+        let mut chain = unsafe { ErrorImpl::chain(this) };
+        let mut owned = unsafe { ErrorImpl::owned(this) };
+        let head = Cause::new(&mut owned, chain.next().unwrap());
+        let mut state = serializer.serialize_struct("Error", 5)?;
+        state.serialize_field("message", &Message(head.error))?;
+        state.serialize_field("type_name", &head.type_name)?;
+        state.serialize_field("location", &head.location)?;
+        state.serialize_field("causes", &Causes::new(chain, owned))?;
+        state.serialize_field("backtrace", &Frames(this))?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(all(feature = "serde", feature = "std"))))]
+impl Serialize for Chain<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut owned = match self.layers() {
+            Some(layers) => unsafe { ErrorImpl::owned(layers) },
+            None => Owned::default(),
+        };
+
+        // Owned errors are matched in chain order, so first catch up with the
+        // errors this chain has already been advanced past.
+        if let Some(layers) = self.layers() {
+            let full: Vec<_> = unsafe { ErrorImpl::chain(layers) }.collect();
+            let rest: Vec<_> = self.clone().collect();
+            let skipped = (0..=full.len() - rest.len())
+                .find(|&k| full[k..].iter().zip(&rest).all(|(a, b)| same_error(*a, *b)))
+                .unwrap_or(0);
+            for error in &full[..skipped] {
+                owned.take(*error);
+            }
+        }
+
+        Causes::new(self.clone(), owned).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "std")]
+fn same_error(a: &(dyn StdError + 'static), b: &(dyn StdError + 'static)) -> bool {
+    a as *const dyn StdError as *const () == b as *const dyn StdError as *const ()
+}
+
+struct Message<'a>(&'a dyn Display);
+
+impl Serialize for Message<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self.0)
+    }
+}
+
+enum SourceLocation<'a> {
+    Local(&'static Location),
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    Recorded(&'a str),
+}
+
+impl Serialize for SourceLocation<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            SourceLocation::Local(location) => serializer.collect_str(location),
+            SourceLocation::Recorded(location) => serializer.serialize_str(location),
+        }
+    }
+}
+
+struct Cause<'a> {
+    error: &'a (dyn StdError + 'static),
+    type_name: Option<&'a str>,
+    location: Option<SourceLocation<'a>>,
+}
+
+impl<'a> Cause<'a> {
+    // To be called with each error of the chain in order.
+    fn new(owned: &mut Owned, error: &'a (dyn StdError + 'static)) -> Self {
+        let owner = owned.take(error);
+
+        #[cfg(feature = "std")]
+        {
+            if let Some(remote) = error.downcast_ref::<RemoteError>() {
+                return Cause {
+                    error,
+                    type_name: remote.type_name(),
+                    location: remote.location().map(SourceLocation::Recorded),
+                };
+            }
+
+            if let Some(panic) = error.downcast_ref::<PanicError>() {
+                return Cause {
+                    error,
+                    type_name: owner.map(|owner| owner.type_name),
+                    location: panic.location().map(SourceLocation::Recorded),
+                };
+            }
+        }
+
+        match owner {
+            Some(owner) => Cause {
+                error,
+                type_name: Some(owner.type_name),
+                location: if owner.head {
+                    unsafe { ErrorImpl::head_location(owner.layer) }.map(SourceLocation::Local)
+                } else {
+                    None
+                },
+            },
+            None => Cause {
+                error,
+                type_name: None,
+                location: None,
+            },
+        }
+    }
+}
+
+impl Serialize for Cause<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("Cause", 3)?;
+        state.serialize_field("message", &Message(self.error))?;
+        state.serialize_field("type_name", &self.type_name)?;
+        state.serialize_field("location", &self.location)?;
+        state.end()
+    }
+}
+
+struct Causes<'a> {
+    causes: Vec<Cause<'a>>,
+}
+
+impl<'a> Causes<'a> {
+    fn new(chain: Chain<'a>, mut owned: Owned) -> Self {
+        let causes = chain.map(|error| Cause::new(&mut owned, error)).collect();
+        Causes { causes }
+    }
+}
+
+impl Serialize for Causes<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(&self.causes)
+    }
+}
+
+struct Frames<'a>(Ref<'a, ErrorImpl>);
+
+impl Serialize for Frames<'_> {
+    #[cfg(feature = "std")]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // An error rebuilt from a remote one reports the backtrace captured
+        // where it originated, which was only kept as text.
+        if let Some(backtrace) = crate::remote::backtrace(unsafe { ErrorImpl::error(self.0) }) {
+            return serializer.serialize_str(backtrace);
+        }
+
+        #[cfg(feature = "backtrace")]
+        {
+            let frames = unsafe { ErrorImpl::frames(self.0) }.map(crate::Backtrace::frames);
+            if let Some(frames) = frames.filter(|frames| frames.len() > 0) {
+                let symbols = frames.enumerate().flat_map(|(index, frame)| {
+                    // A frame without debug information is listed once, with
+                    // no function.
+                    let symbols = frame.symbols();
+                    (0..cmp::max(1, symbols.len())).map(move |i| Symbol {
+                        index,
+                        symbol: symbols.get(i),
+                    })
+                });
+                return serializer.collect_seq(symbols);
+            }
+        }
+
+        #[cfg(std_backtrace)]
+        {
+            use crate::backtrace::BacktraceStatus;
+
+            let backtrace = unsafe { ErrorImpl::backtrace(self.0) };
+            if let BacktraceStatus::Captured = backtrace.status() {
+                return serializer.collect_str(backtrace);
+            }
+        }
+
+        serializer.serialize_none()
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let _ = self.0;
+        serializer.serialize_none()
+    }
+}
+
+// A function that a frame of the backtrace is executing. Functions inlined
+// into the same frame share its index.
+#[cfg(feature = "backtrace")]
+struct Symbol<'a> {
+    index: usize,
+    symbol: Option<&'a BacktraceSymbol>,
+}
+
+#[cfg(feature = "backtrace")]
+impl Serialize for Symbol<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let symbol = self.symbol;
+        let file = symbol.and_then(BacktraceSymbol::printed_filename);
+        let mut state = serializer.serialize_struct("Frame", 5)?;
+        state.serialize_field("index", &self.index)?;
+        state.serialize_field("function", &symbol.and_then(BacktraceSymbol::name))?;
+        state.serialize_field("file", &file)?;
+        state.serialize_field("line", &symbol.and_then(BacktraceSymbol::lineno))?;
+        state.serialize_field("column", &symbol.and_then(BacktraceSymbol::colno))?;
+        state.end()
+    }
+}
@@ -0,0 +1,61 @@
+use crate::{Error, SharedError, StdError};
+use core::fmt::{self, Debug, Display};
+use core::ops::Deref;
+use std::sync::Arc;
+
+impl Deref for SharedError {
+    type Target = Error;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl From<Error> for SharedError {
+    fn from(error: Error) -> Self {
+        SharedError {
+            inner: Arc::new(error),
+        }
+    }
+}
+
+impl From<SharedError> for Error {
+    #[cold]
+    fn from(error: SharedError) -> Self {
+        match Arc::try_unwrap(error.inner) {
+            // This was the last handle; the original error is ours again.
+            Ok(error) => error,
+            Err(inner) => Error::from_shared(SharedError { inner }),
+        }
+    }
+}
+
+impl Display for SharedError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&*self.inner, formatter)
+    }
+}
+
+impl Debug for SharedError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        Debug::fmt(&*self.inner, formatter)
+    }
+}
+
+impl AsRef<Error> for SharedError {
+    fn as_ref(&self) -> &Error {
+        &self.inner
+    }
+}
+
+impl AsRef<dyn StdError + Send + Sync> for SharedError {
+    fn as_ref(&self) -> &(dyn StdError + Send + Sync + 'static) {
+        &**self.inner
+    }
+}
+
+impl AsRef<dyn StdError> for SharedError {
+    fn as_ref(&self) -> &(dyn StdError + 'static) {
+        &**self.inner
+    }
+}
@@ -0,0 +1,563 @@
+use crate::{BacktraceSymbol, Error, Module, UnresolvedBacktrace, UnresolvedFrame};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Write};
+use core::str::FromStr;
+
+// First line of the text form, to recognize it and to allow changing it.
+const HEADER: &str = "unresolved backtrace v1";
+
+impl UnresolvedBacktrace {
+    pub(crate) fn new(ips: &[usize]) -> Self {
+        // Unwinding sometimes goes one frame past the outermost one. The
+        // return address of a call is the instruction after it, which may
+        // belong to the next line or even to the next function.
+        let addresses: Vec<usize> = ips
+            .iter()
+            .filter(|&&ip| ip != 0)
+            .map(|&ip| ip - 1)
+            .collect();
+        let (mut loaded, found) = loaded::locate(&addresses);
+
+        // Number the modules in the order the frames first point into them.
+        let mut used: Vec<usize> = Vec::new();
+        let mut modules = Vec::new();
+        let mut frames = Vec::new();
+        for (&address, found) in addresses.iter().zip(found) {
+            let frame = match found {
+                Some(found) => {
+                    let module = match used.iter().position(|&i| i == found.module) {
+                        Some(module) => module,
+                        None => {
+                            used.push(found.module);
+                            modules.push(loaded[found.module].take().unwrap());
+                            modules.len() - 1
+                        }
+                    };
+                    UnresolvedFrame {
+                        module: Some(module),
+                        address: (address - found.bias) as u64,
+                    }
+                }
+                None => UnresolvedFrame {
+                    module: None,
+                    address: address as u64,
+                },
+            };
+            frames.push(frame);
+        }
+        UnresolvedBacktrace { modules, frames }
+    }
+
+    /// The modules that the frames point into.
+    pub fn modules(&self) -> &[Module] {
+        &self.modules
+    }
+
+    /// The frames of the backtrace, innermost first, starting from the place
+    /// where the error was created.
+    pub fn frames(&self) -> &[UnresolvedFrame] {
+        &self.frames
+    }
+
+    /// Resolves the frames to functions and source locations, and prints them
+    /// like the backtrace of an error.
+    ///
+    /// The `resolve` function is called with each frame's module and address,
+    /// and returns the symbols for the code there, usually by looking up the
+    /// address in the debug information of the module with the same
+    /// [build ID][Module::build_id], for example with the `addr2line` crate.
+    /// As for [`BacktraceFrame::symbols`][crate::BacktraceFrame::symbols],
+    /// there is one symbol for each function inlined at that point, innermost
+    /// first. Frames outside of any known module are not passed to `resolve`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anyhow::{BacktraceSymbol, UnresolvedBacktrace};
+    /// use std::path::Path;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let text = "\
+    ///     unresolved backtrace v1\n\
+    ///     module 4a0b5c6d /usr/bin/server\n\
+    ///     frames 0+0x1a2b 0+0x1c40\n";
+    /// let backtrace: UnresolvedBacktrace = text.parse()?;
+    ///
+    /// let printed = backtrace.symbolize(|module, address| {
+    ///     assert_eq!(module.build_id(), Some(&[0x4a, 0x0b, 0x5c, 0x6d][..]));
+    ///     match address {
+    ///         0x1a2b => vec![BacktraceSymbol::new(
+    ///             Some("server::handle"),
+    ///             Some(Path::new("src/handle.rs")),
+    ///             Some(12),
+    ///             Some(5),
+    ///         )],
+    ///         _ => Vec::new(),
+    ///     }
+    /// });
+    /// assert_eq!(
+    ///     printed,
+    ///     "   0: server::handle\n             at src/handle.rs:12:5\n   1: <unknown>\n",
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn symbolize<F>(&self, mut resolve: F) -> String
+    where
+        F: FnMut(&Module, u64) -> Vec<BacktraceSymbol>,
+    {
+        let mut printed = String::new();
+        for (index, frame) in self.frames.iter().enumerate() {
+            let symbols = match frame.module {
+                Some(module) => resolve(&self.modules[module], frame.address),
+                None => Vec::new(),
+            };
+            if symbols.is_empty() {
+                let _ = writeln!(printed, "{:>4}: <unknown>", index);
+            }
+            for (i, symbol) in symbols.iter().enumerate() {
+                if i == 0 {
+                    let _ = write!(printed, "{:>4}: ", index);
+                } else {
+                    printed.push_str("      ");
+                }
+                match symbol.name() {
+                    Some(name) => printed.push_str(&name),
+                    None => printed.push_str("<unknown>"),
+                }
+                printed.push('\n');
+                if let (Some(file), Some(line)) = (symbol.filename(), symbol.lineno()) {
+                    let _ = write!(printed, "             at {}:{}", file.display(), line);
+                    if let Some(column) = symbol.colno() {
+                        let _ = write!(printed, ":{}", column);
+                    }
+                    printed.push('\n');
+                }
+            }
+        }
+        printed
+    }
+}
+
+impl Module {
+    /// The path of the executable or shared library when the backtrace was
+    /// captured.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The build ID of the module, which identifies the build that produced
+    /// it, and thereby the debug information that belongs to it.
+    pub fn build_id(&self) -> Option<&[u8]> {
+        self.build_id.as_ref().map(Vec::as_slice)
+    }
+}
+
+impl UnresolvedFrame {
+    /// The index of the frame's module in
+    /// [`UnresolvedBacktrace::modules`], if the module is known.
+    pub fn module(&self) -> Option<usize> {
+        self.module
+    }
+
+    /// The address of the frame's code in its module's ELF file, which is
+    /// where the module's symbols and debug information place it, or its
+    /// address in memory if the module is not known.
+    ///
+    /// This is the address in memory minus the load bias of the module. It is
+    /// not an offset within the file, which may differ from it by the
+    /// alignment of the module's segments.
+    ///
+    /// For every frame but the innermost one, this points into the call
+    /// instruction that is in progress.
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+}
+
+// One line for the header, one for each module with its build ID in hex, and
+// one with all the frames:
+//
+//     unresolved backtrace v1
+//     module 8d2c7f0e... /usr/bin/server
+//     module - /lib/x86_64-linux-gnu/libc.so.6
+//     frames 0+0x1a2b 0+0x1c40 1+0x29d8f 0x7ffd1e3b5000
+impl Display for UnresolvedBacktrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        for module in &self.modules {
+            f.write_str("module ")?;
+            match &module.build_id {
+                Some(build_id) => {
+                    for byte in build_id {
+                        write!(f, "{:02x}", byte)?;
+                    }
+                }
+                None => f.write_str("-")?,
+            }
+            writeln!(f, " {}", module.path)?;
+        }
+        f.write_str("frames")?;
+        for frame in &self.frames {
+            match frame.module {
+                Some(module) => write!(f, " {}+{:#x}", module, frame.address)?,
+                None => write!(f, " {:#x}", frame.address)?,
+            }
+        }
+        writeln!(f)
+    }
+}
+
+impl FromStr for UnresolvedBacktrace {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut lines = s.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(Error::msg("not an unresolved backtrace"));
+        }
+
+        let mut modules = Vec::new();
+        let mut frames = None;
+        for line in lines {
+            let mut parts = line.splitn(2, ' ');
+            match (parts.next(), parts.next()) {
+                (Some("module"), Some(rest)) if frames.is_none() => {
+                    let mut parts = rest.splitn(2, ' ');
+                    let (build_id, path) = match (parts.next(), parts.next()) {
+                        (Some(build_id), Some(path)) => (build_id, path),
+                        _ => return Err(Error::msg(format!("malformed module: {}", line))),
+                    };
+                    let build_id =
+                        match build_id {
+                            "-" => None,
+                            hex => Some(parse_hex(hex).ok_or_else(|| {
+                                Error::msg(format!("malformed build ID: {}", hex))
+                            })?),
+                        };
+                    modules.push(Module {
+                        path: path.to_owned(),
+                        build_id,
+                    });
+                }
+                (Some("frames"), rest) if frames.is_none() => {
+                    let rest = rest.unwrap_or("");
+                    let parsed = rest
+                        .split_whitespace()
+                        .map(|frame| parse_frame(frame, modules.len()))
+                        .collect::<Option<Vec<_>>>();
+                    frames = Some(
+                        parsed.ok_or_else(|| Error::msg(format!("malformed frames: {}", rest)))?,
+                    );
+                }
+                (Some(""), None) => {}
+                _ => return Err(Error::msg(format!("unexpected line: {}", line))),
+            }
+        }
+
+        match frames {
+            Some(frames) => Ok(UnresolvedBacktrace { modules, frames }),
+            None => Err(Error::msg("missing frames")),
+        }
+    }
+}
+
+fn parse_frame(frame: &str, modules: usize) -> Option<UnresolvedFrame> {
+    let mut parts = frame.splitn(2, '+');
+    match (parts.next(), parts.next()) {
+        (Some(module), Some(address)) => {
+            let module = module.parse().ok().filter(|&module| module < modules)?;
+            Some(UnresolvedFrame {
+                module: Some(module),
+                address: parse_address(address)?,
+            })
+        }
+        (Some(address), None) => Some(UnresolvedFrame {
+            module: None,
+            address: parse_address(address)?,
+        }),
+        _ => None,
+    }
+}
+
+fn parse_address(address: &str) -> Option<u64> {
+    if address.starts_with("0x") {
+        u64::from_str_radix(&address[2..], 16).ok()
+    } else {
+        None
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.is_empty() || hex.len() % 2 != 0 || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+// The executables and shared libraries loaded into the process, found with
+// dl_iterate_phdr(3). The backtrace crate does not tell which module a frame
+// belongs to on Linux, so the program headers are read here.
+//
+// The callback runs with the dynamic loader's lock held, so it does not
+// allocate. A first pass finds the modules that the addresses point into and
+// the sizes of their names and build IDs, and a second pass copies those into
+// buffers allocated in between.
+#[cfg(target_os = "linux")]
+mod loaded {
+    use crate::Module;
+    use core::ffi::c_void;
+    use core::slice;
+    use std::env;
+    use std::ffi::CStr;
+    use std::os::raw::{c_char, c_int};
+
+    pub(super) struct Found {
+        // Index of the module in the ones returned by `locate`.
+        pub(super) module: usize,
+        // Difference between the addresses in the file and in memory.
+        pub(super) bias: usize,
+    }
+
+    // A module that some address points into.
+    struct Used {
+        // Position of the module in the iteration over all modules.
+        ordinal: usize,
+        bias: usize,
+        name: Vec<u8>,
+        name_len: usize,
+        build_id: Vec<u8>,
+        build_id_len: Option<usize>,
+    }
+
+    struct Locate<'a> {
+        addresses: &'a [usize],
+        found: &'a mut [Option<Found>],
+        used: &'a mut Vec<Used>,
+        ordinal: usize,
+    }
+
+    #[repr(C)]
+    struct DlPhdrInfo {
+        addr: usize,
+        name: *const c_char,
+        phdr: *const Phdr,
+        phnum: u16,
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[repr(C)]
+    struct Phdr {
+        p_type: u32,
+        p_flags: u32,
+        p_offset: usize,
+        p_vaddr: usize,
+        p_paddr: usize,
+        p_filesz: usize,
+        p_memsz: usize,
+        p_align: usize,
+    }
+
+    #[cfg(target_pointer_width = "32")]
+    #[repr(C)]
+    struct Phdr {
+        p_type: u32,
+        p_offset: usize,
+        p_vaddr: usize,
+        p_paddr: usize,
+        p_filesz: usize,
+        p_memsz: usize,
+        p_flags: u32,
+        p_align: usize,
+    }
+
+    const PT_LOAD: u32 = 1;
+    const PT_NOTE: u32 = 4;
+    const NT_GNU_BUILD_ID: u32 = 3;
+
+    type Callback = extern "C" fn(*mut DlPhdrInfo, usize, *mut c_void) -> c_int;
+
+    extern "C" {
+        fn dl_iterate_phdr(callback: Callback, data: *mut c_void) -> c_int;
+    }
+
+    // Finds the module that each address points into. Each module is `Some`
+    // in the returned list, to be taken by the caller.
+    pub(super) fn locate(addresses: &[usize]) -> (Vec<Option<Module>>, Vec<Option<Found>>) {
+        let mut found: Vec<Option<Found>> = addresses.iter().map(|_| None).collect();
+        let mut used = Vec::with_capacity(addresses.len());
+        let mut state = Locate {
+            addresses,
+            found: &mut found,
+            used: &mut used,
+            ordinal: 0,
+        };
+        iterate(find, &mut state);
+
+        for used in state.used.iter_mut() {
+            used.name = Vec::with_capacity(used.name_len);
+            used.build_id = Vec::with_capacity(used.build_id_len.unwrap_or(0));
+        }
+        state.ordinal = 0;
+        iterate(copy, &mut state);
+
+        let modules = used
+            .into_iter()
+            .map(|used| {
+                // The executable itself is listed without a name.
+                let path = if used.name_len == 0 {
+                    env::current_exe()
+                        .map(|path| path.to_string_lossy().into_owned())
+                        .unwrap_or_default()
+                } else {
+                    String::from_utf8_lossy(&used.name).into_owned()
+                };
+                // Only if the second pass saw the same module as the first.
+                let build_id = match used.build_id_len {
+                    Some(len) if len == used.build_id.len() => Some(used.build_id),
+                    _ => None,
+                };
+                Some(Module { path, build_id })
+            })
+            .collect();
+        (modules, found)
+    }
+
+    fn iterate(callback: Callback, state: &mut Locate) {
+        unsafe {
+            dl_iterate_phdr(callback, state as *mut Locate as *mut c_void);
+        }
+    }
+
+    // Must not panic, since it is called from C.
+    extern "C" fn find(info: *mut DlPhdrInfo, _size: usize, data: *mut c_void) -> c_int {
+        let state = unsafe { &mut *(data as *mut Locate) };
+        let info = unsafe { &*info };
+        let ordinal = state.ordinal;
+        state.ordinal += 1;
+
+        for phdr in phdrs(info) {
+            if phdr.p_type != PT_LOAD {
+                continue;
+            }
+            let start = info.addr.wrapping_add(phdr.p_vaddr);
+            let end = start.wrapping_add(phdr.p_memsz);
+            for (i, &address) in state.addresses.iter().enumerate() {
+                if state.found[i].is_some() || address < start || address >= end {
+                    continue;
+                }
+                if state.used.last().map(|used| used.ordinal) != Some(ordinal) {
+                    // At most one push per address, within the capacity.
+                    state.used.push(Used {
+                        ordinal,
+                        bias: info.addr,
+                        name: Vec::new(),
+                        name_len: name(info).len(),
+                        build_id: Vec::new(),
+                        build_id_len: build_id(info).map(<[u8]>::len),
+                    });
+                }
+                state.found[i] = Some(Found {
+                    module: state.used.len() - 1,
+                    bias: info.addr,
+                });
+            }
+        }
+        0
+    }
+
+    // Must not panic, since it is called from C.
+    extern "C" fn copy(info: *mut DlPhdrInfo, _size: usize, data: *mut c_void) -> c_int {
+        let state = unsafe { &mut *(data as *mut Locate) };
+        let info = unsafe { &*info };
+        let ordinal = state.ordinal;
+        state.ordinal += 1;
+
+        let used = match state.used.iter_mut().find(|used| used.ordinal == ordinal) {
+            Some(used) if used.bias == info.addr => used,
+            _ => return 0,
+        };
+        // Copying within the capacity does not allocate. A module loaded or
+        // unloaded in between may not fit, and is then left without its name
+        // or build ID.
+        let name = name(info);
+        if name.len() == used.name.capacity() {
+            used.name.extend_from_slice(name);
+        }
+        if let Some(build_id) = build_id(info) {
+            if Some(build_id.len()) == used.build_id_len {
+                used.build_id.extend_from_slice(build_id);
+            }
+        }
+        0
+    }
+
+    fn phdrs(info: &DlPhdrInfo) -> &[Phdr] {
+        if info.phdr.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(info.phdr, info.phnum as usize) }
+        }
+    }
+
+    fn name(info: &DlPhdrInfo) -> &[u8] {
+        if info.name.is_null() {
+            &[]
+        } else {
+            unsafe { CStr::from_ptr(info.name) }.to_bytes()
+        }
+    }
+
+    fn build_id(info: &DlPhdrInfo) -> Option<&[u8]> {
+        phdrs(info)
+            .iter()
+            .filter(|phdr| phdr.p_type == PT_NOTE)
+            .find_map(|phdr| {
+                let start = info.addr.wrapping_add(phdr.p_vaddr);
+                let notes = unsafe { slice::from_raw_parts(start as *const u8, phdr.p_memsz) };
+                find_build_id(notes, phdr.p_align)
+            })
+    }
+
+    // Each note is a header of three 32-bit words, the size of its name, the
+    // size of its descriptor and its type, followed by the name and by the
+    // descriptor, each padded to the alignment of the segment.
+    fn find_build_id(mut notes: &[u8], align: usize) -> Option<&[u8]> {
+        let align = if align == 8 { 8 } else { 4 };
+        let padded = |size: usize| size.checked_add(align - 1).map(|size| size & !(align - 1));
+        let word = |bytes: &[u8], i: usize| {
+            u32::from_ne_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]) as usize
+        };
+        while notes.len() >= 12 {
+            let (namesz, descsz, kind) = (word(notes, 0), word(notes, 4), word(notes, 8));
+            let name_end = padded(namesz)?.checked_add(12)?;
+            let desc_end = name_end.checked_add(padded(descsz)?)?;
+            if desc_end > notes.len() {
+                return None;
+            }
+            if kind == NT_GNU_BUILD_ID as usize && &notes[12..12 + namesz] == b"GNU\0" {
+                return Some(&notes[name_end..name_end + descsz]);
+            }
+            notes = &notes[desc_end..];
+        }
+        None
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod loaded {
+    use crate::Module;
+
+    pub(super) struct Found {
+        pub(super) module: usize,
+        pub(super) bias: usize,
+    }
+
+    pub(super) fn locate(addresses: &[usize]) -> (Vec<Option<Module>>, Vec<Option<Found>>) {
+        (Vec::new(), addresses.iter().map(|_| None).collect())
+    }
+}
@@ -1,8 +1,13 @@
 use crate::StdError;
 use core::fmt::{self, Debug, Display};
 
-#[cfg(backtrace)]
-use std::any::Demand;
+#[cfg(feature = "std")]
+use crate::SharedError;
+
+#[cfg(error_generic_member_access)]
+use crate::Error;
+#[cfg(error_generic_member_access)]
+use std::error::Request;
 
 #[repr(transparent)]
 pub struct MessageError<M>(pub M);
@@ -74,8 +79,38 @@ impl StdError for BoxedError {
         self.0.source()
     }
 
-    #[cfg(backtrace)]
-    fn provide<'a>(&'a self, demand: &mut Demand<'a>) {
-        self.0.provide(demand);
+    #[cfg(error_generic_member_access)]
+    fn provide<'a>(&'a self, request: &mut Request<'a>) {
+        self.0.provide(request);
+    }
+}
+
+#[cfg(feature = "std")]
+#[repr(transparent)]
+pub struct SharedWrapper(pub SharedError);
+
+#[cfg(feature = "std")]
+impl Debug for SharedWrapper {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Debug::fmt(&**self.0, f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Display for SharedWrapper {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&**self.0, f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl StdError for SharedWrapper {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.0.source()
+    }
+
+    #[cfg(error_generic_member_access)]
+    fn provide<'a>(&'a self, request: &mut Request<'a>) {
+        Error::provide(&self.0, request);
     }
 }
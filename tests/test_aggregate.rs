@@ -0,0 +1,158 @@
+#![cfg(feature = "std")]
+
+mod drop;
+
+use self::drop::{DetectDrop, Flag};
+use anyhow::{anyhow, Context, Error};
+use std::io;
+
+fn io_error() -> Error {
+    Err::<(), _>(io::Error::new(io::ErrorKind::Other, "oh no!"))
+        .context("f failed")
+        .context("g failed")
+        .unwrap_err()
+}
+
+fn error() -> Error {
+    Error::aggregate(vec![io_error(), anyhow!("bad row")])
+}
+
+const EXPECTED_DEBUG: &str = "\
+2 errors occurred
+
+Caused by:
+    0: g failed
+       Caused by:
+           0: f failed
+           1: oh no!
+    1: bad row\
+";
+
+const EXPECTED_DEBUG_CONTEXT: &str = "\
+batch failed
+
+Caused by:
+    2 errors occurred
+    Caused by:
+        0: g failed
+           Caused by:
+               0: f failed
+               1: oh no!
+        1: bad row\
+";
+
+#[test]
+fn test_display() {
+    assert_eq!("2 errors occurred", error().to_string());
+    assert_eq!(
+        "1 error occurred",
+        Error::aggregate(vec![io_error()]).to_string()
+    );
+    assert_eq!(
+        "batch failed: 2 errors occurred",
+        format!("{:#}", error().context("batch failed"))
+    );
+}
+
+// Leaves out the backtrace, if one was captured.
+fn debug(error: &Error) -> String {
+    let debug = format!("{:?}", error);
+    debug
+        .split("\n\nStack backtrace:")
+        .next()
+        .unwrap()
+        .to_owned()
+}
+
+#[test]
+fn test_debug() {
+    assert_eq!(EXPECTED_DEBUG, debug(&error()));
+    assert_eq!(
+        EXPECTED_DEBUG_CONTEXT,
+        debug(&error().context("batch failed"))
+    );
+}
+
+#[test]
+fn test_children() {
+    let error = error().context("batch failed");
+    let children = error.children();
+    assert_eq!(2, children.len());
+    assert_eq!("g failed", children[0].to_string());
+    assert_eq!("bad row", children[1].to_string());
+    assert!(anyhow!("oh no!").children().is_empty());
+}
+
+#[test]
+fn test_downcast() {
+    let mut error = error();
+    assert!(error.is::<io::Error>());
+    assert_eq!("g failed", *error.downcast_ref::<&str>().unwrap());
+    assert!(error.downcast_mut::<io::Error>().is_some());
+    assert!(error.downcast::<io::Error>().is_ok());
+}
+
+#[test]
+fn test_drop() {
+    let first = Flag::new();
+    let second = Flag::new();
+    let error = Error::aggregate(vec![
+        Error::new(DetectDrop::new(&first)),
+        Error::new(DetectDrop::new(&second)).context("context"),
+    ]);
+    drop(error);
+    assert!(first.get() && second.get());
+
+    let first = Flag::new();
+    let second = Flag::new();
+    let error = Error::aggregate(vec![
+        anyhow!("oh no!").context("context"),
+        Error::new(DetectDrop::new(&first)),
+        Error::new(DetectDrop::new(&second)),
+    ]);
+    let detect = error.downcast::<DetectDrop>().unwrap();
+    assert!(!first.get() && second.get());
+    drop(detect);
+    assert!(first.get());
+}
+
+#[test]
+fn test_try_collect_all() {
+    use anyhow::IteratorExt;
+
+    let results = vec![Ok(1), Err(io_error()), Ok(3), Err(anyhow!("bad row"))];
+    let mut polled = 0;
+    let error = results
+        .into_iter()
+        .inspect(|_| polled += 1)
+        .try_collect_all::<Vec<i32>, _, _>()
+        .unwrap_err();
+    assert_eq!(4, polled);
+    assert_eq!("2 errors occurred", error.to_string());
+    let children = error.children();
+    assert_eq!(
+        "item 1: g failed: f failed: oh no!",
+        format!("{:#}", children[0])
+    );
+    assert_eq!("item 3: bad row", format!("{:#}", children[1]));
+    assert!(error.is::<io::Error>());
+
+    let expected = "\
+2 errors occurred
+
+Caused by:
+    0: item 1
+       Caused by:
+           0: g failed
+           1: f failed
+           2: oh no!
+    1: item 3
+       Caused by:
+           bad row\
+";
+    assert_eq!(expected, debug(&error));
+
+    let results = vec![Ok::<_, io::Error>(1), Ok(2)];
+    let collected: Vec<i32> = anyhow::try_collect_all(results).unwrap();
+    assert_eq!(vec![1, 2], collected);
+}
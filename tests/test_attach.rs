@@ -0,0 +1,75 @@
+#![cfg(feature = "std")]
+
+mod drop;
+
+use self::drop::{DetectDrop, Flag};
+use anyhow::{anyhow, Context, Error};
+use std::io;
+use std::time::Duration;
+
+#[derive(Debug, PartialEq)]
+struct RequestId(u64);
+
+#[derive(Debug, PartialEq)]
+struct RetryAfter(Duration);
+
+#[test]
+fn test_request_ref() {
+    let error = anyhow!("oh no!").attach(RequestId(1));
+    assert_eq!(Some(&RequestId(1)), error.request_ref::<RequestId>());
+    assert_eq!(None, error.request_ref::<RetryAfter>());
+}
+
+#[test]
+fn test_context_layers() {
+    let error = Err::<(), _>(io::Error::new(io::ErrorKind::Other, "oh no!"))
+        .context("f failed")
+        .map_err(|error| error.attach(RequestId(1)))
+        .context("g failed")
+        .unwrap_err()
+        .attach(RetryAfter(Duration::from_secs(5)));
+
+    assert_eq!(Some(&RequestId(1)), error.request_ref::<RequestId>());
+    assert_eq!(
+        Some(&RetryAfter(Duration::from_secs(5))),
+        error.request_ref::<RetryAfter>(),
+    );
+
+    // Attachments do not change how the error is rendered.
+    assert_eq!("g failed: f failed: oh no!", format!("{:#}", error));
+}
+
+#[test]
+fn test_latest_wins() {
+    let error = anyhow!("oh no!")
+        .attach(RequestId(1))
+        .context("context")
+        .attach(RequestId(2));
+    assert_eq!(Some(&RequestId(2)), error.request_ref::<RequestId>());
+
+    let error = anyhow!("oh no!").attach(RequestId(1)).attach(RequestId(2));
+    assert_eq!(Some(&RequestId(2)), error.request_ref::<RequestId>());
+}
+
+#[test]
+fn test_shared() {
+    let shared = anyhow!("oh no!").attach(RequestId(1)).into_shared();
+    let error = Error::from(shared.clone()).context("context");
+    assert_eq!(Some(&RequestId(1)), error.request_ref::<RequestId>());
+    assert_eq!(Some(&RequestId(1)), shared.request_ref::<RequestId>());
+}
+
+#[test]
+fn test_drop() {
+    let has_dropped = Flag::new();
+    let error = anyhow!("oh no!").attach(DetectDrop::new(&has_dropped));
+    drop(error);
+    assert!(has_dropped.get());
+
+    let has_dropped = Flag::new();
+    let error = anyhow!("oh no!")
+        .attach(DetectDrop::new(&has_dropped))
+        .context("context");
+    let _ = error.downcast::<&str>().unwrap();
+    assert!(has_dropped.get());
+}
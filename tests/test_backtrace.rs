@@ -1,13 +1,58 @@
-#[rustversion::not(nightly)]
+#![cfg(feature = "std")]
+
+#[rustversion::before(1.65)]
 #[ignore]
 #[test]
 fn test_backtrace() {}
 
-#[rustversion::nightly]
+#[rustversion::since(1.65)]
 #[test]
 fn test_backtrace() {
     use anyhow::anyhow;
+    use std::backtrace::{Backtrace, BacktraceStatus};
 
     let error = anyhow!("oh no!");
-    let _ = error.backtrace();
+    let backtrace: &Backtrace = error.backtrace();
+    let enabled = Backtrace::capture().status();
+    if cfg!(feature = "backtrace") {
+        // The backtrace is captured with the frames instead.
+        assert_eq!(BacktraceStatus::Disabled, backtrace.status());
+    } else {
+        assert_eq!(enabled, backtrace.status());
+    }
+
+    let debug = format!("{:?}", error);
+    let captured = enabled == BacktraceStatus::Captured;
+    assert_eq!(captured, debug.contains("\n\nStack backtrace:\n"));
+
+    // Adding context keeps the original backtrace.
+    let address = backtrace as *const Backtrace;
+    let error = error.context("f failed");
+    assert_eq!(address, error.backtrace() as *const Backtrace);
+}
+
+#[rustversion::since(1.65)]
+#[test]
+fn test_with_backtrace() {
+    use anyhow::{anyhow, Error};
+    use std::backtrace::Backtrace;
+    use std::{io, thread};
+
+    let backtrace = thread::spawn(Backtrace::force_capture).join().unwrap();
+    let rendered = backtrace.to_string();
+    let error = anyhow!("oh no!").with_backtrace(backtrace);
+    assert_eq!(rendered, error.backtrace().to_string());
+    let debug = format!("{:?}", error);
+    assert!(debug.contains("\n\nStack backtrace:\n"), "{}", debug);
+
+    // Adding context keeps the backtrace from the other thread.
+    let error = error.context("f failed");
+    assert_eq!(rendered, error.backtrace().to_string());
+
+    let backtrace = thread::spawn(Backtrace::force_capture).join().unwrap();
+    let rendered = backtrace.to_string();
+    let error = io::Error::new(io::ErrorKind::Other, "oh no!");
+    let error = Error::from_parts(error, backtrace);
+    assert!(error.is::<io::Error>());
+    assert_eq!(rendered, error.backtrace().to_string());
 }
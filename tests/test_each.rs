@@ -0,0 +1,63 @@
+#![cfg(feature = "std")]
+
+use anyhow::{anyhow, Error, IteratorExt};
+use std::io;
+
+fn items() -> Vec<Result<u32, io::Error>> {
+    vec![
+        Ok(1),
+        Err(io::Error::new(io::ErrorKind::Other, "oh no!")),
+        Ok(3),
+        Err(io::Error::new(io::ErrorKind::Other, "oh no!")),
+    ]
+}
+
+#[test]
+fn test_context_each() {
+    let mut called = Vec::new();
+    let results: Vec<_> = items()
+        .into_iter()
+        .context_each(|index| {
+            called.push(index);
+            format!("item {}", index)
+        })
+        .collect();
+    assert_eq!(vec![1, 3], called);
+
+    assert_eq!(1, *results[0].as_ref().unwrap());
+    let error = results[1].as_ref().unwrap_err();
+    assert_eq!("item 1: oh no!", format!("{:#}", error));
+    assert!(error.root_cause().is::<io::Error>());
+    assert_eq!(Some(&"item 1".to_owned()), error.downcast_ref::<String>());
+    let error = results[3].as_ref().unwrap_err();
+    assert_eq!("item 3: oh no!", format!("{:#}", error));
+}
+
+#[test]
+fn test_anyhow_errors() {
+    let line = line!() + 3;
+    let error = vec![Ok(()), Err::<(), Error>(anyhow!("oh no!"))]
+        .into_iter()
+        .context_each(|index| format!("item {}", index))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_err();
+    assert_eq!("item 1: oh no!", format!("{:#}", error));
+    assert_eq!(line, error.locations().next().unwrap().line());
+}
+
+#[cfg(feature = "futures-core")]
+#[test]
+fn test_stream() {
+    use anyhow::StreamExt as _;
+    use futures::stream::{self, StreamExt as _};
+    use futures::FutureExt as _;
+
+    let results: Vec<_> = stream::iter(items())
+        .context_each(|index| format!("item {}", index))
+        .collect()
+        .now_or_never()
+        .unwrap();
+    assert_eq!(4, results.len());
+    let error = results[3].as_ref().unwrap_err();
+    assert_eq!("item 3: oh no!", format!("{:#}", error));
+}
@@ -0,0 +1,62 @@
+#![cfg(feature = "std")]
+
+use anyhow::{anyhow, Context, Error, Result};
+use std::io;
+
+fn read() -> Result<(), io::Error> {
+    Err(io::Error::new(io::ErrorKind::Other, "oh no!"))
+}
+
+fn fields(error: &Error) -> Vec<String> {
+    error.fields().map(|field| field.to_string()).collect()
+}
+
+#[test]
+fn test_with_field() {
+    let error = anyhow!("oh no!")
+        .with_field("user", 3)
+        .with_field("order", "A-7")
+        .context("failed to place order")
+        .with_field("attempt", 2);
+    assert_eq!(
+        "attempt=2: failed to place order: order=A-7 user=3: oh no!",
+        format!("{:#}", error),
+    );
+    assert_eq!(vec!["attempt=2", "order=A-7", "user=3"], fields(&error));
+
+    let field = error.fields().nth(2).unwrap();
+    assert_eq!("user", field.key());
+    assert_eq!("3", field.value().to_string());
+    assert_eq!(Some(&3), field.downcast_ref::<i32>());
+    assert_eq!(None, field.downcast_ref::<u32>());
+}
+
+#[test]
+fn test_context_kv() {
+    let error = read()
+        .context_kv("path", "/etc/app.toml")
+        .context_kv("retry", false)
+        .unwrap_err();
+    assert_eq!(
+        "retry=false path=/etc/app.toml: oh no!",
+        format!("{:#}", error)
+    );
+    assert_eq!(vec!["retry=false", "path=/etc/app.toml"], fields(&error));
+    assert!(error.root_cause().is::<io::Error>());
+    assert!(error.is::<io::Error>());
+
+    let error = None::<()>.context_kv("key", "missing").unwrap_err();
+    assert_eq!("key=missing", error.to_string());
+    let error = error.with_field("table", "users");
+    assert_eq!("table=users key=missing", error.to_string());
+    assert_eq!(vec!["table=users", "key=missing"], fields(&error));
+}
+
+#[test]
+fn test_debug() {
+    let error = anyhow!("oh no!").with_field("user", 3);
+    let debug = format!("{:?}", error);
+    assert!(debug.starts_with("user=3\n"), "{}", debug);
+    assert!(debug.contains("\n\nCaused by:\n    oh no!"), "{}", debug);
+    assert!(anyhow!("oh no!").fields().next().is_none());
+}
@@ -0,0 +1,120 @@
+#![cfg(feature = "std")]
+
+use anyhow::{anyhow, Context, Error, Fingerprint};
+use std::io;
+
+fn io_error(message: &str) -> Error {
+    Err::<(), _>(io::Error::new(io::ErrorKind::Other, message.to_owned()))
+        .context(format!("failed to read {}", "config.toml"))
+        .unwrap_err()
+}
+
+#[test]
+fn test_deterministic() {
+    assert_eq!(
+        anyhow!("oh no!").fingerprint(),
+        anyhow!("oh no!").fingerprint(),
+    );
+    assert_eq!(
+        io_error("oh no!").fingerprint(),
+        io_error("oh no!").fingerprint()
+    );
+}
+
+#[test]
+fn test_volatile_values() {
+    let a = anyhow!("request 1193 timed out at 0x7ffd3a8c");
+    let b = anyhow!("request 20 timed out at 0x55d1e010");
+    assert_eq!(a.fingerprint(), b.fingerprint());
+
+    let a = io_error("disk 1 is 98% full");
+    let b = io_error("disk 3 is 100% full");
+    assert_eq!(a.fingerprint(), b.fingerprint());
+}
+
+#[test]
+fn test_digits_in_words() {
+    let fingerprint = anyhow!("2fa failed").fingerprint();
+    assert_ne!(fingerprint, anyhow!("7 failed").fingerprint());
+    assert_ne!(fingerprint, anyhow!("3fa failed").fingerprint());
+    assert_ne!(
+        anyhow!("no route over ipv4").fingerprint(),
+        anyhow!("no route over ipv6").fingerprint(),
+    );
+    assert_eq!(
+        anyhow!("retry #1 of 3").fingerprint(),
+        anyhow!("retry #2 of 5").fingerprint(),
+    );
+}
+
+#[test]
+fn test_different() {
+    let fingerprint = anyhow!("oh no!").fingerprint();
+    assert_ne!(fingerprint, anyhow!("oh yes!").fingerprint());
+    assert_ne!(
+        fingerprint,
+        anyhow!("oh no!").context("f failed").fingerprint()
+    );
+    assert_ne!(fingerprint, anyhow!(String::from("oh no!")).fingerprint());
+    assert_ne!(
+        io_error("oh no!").fingerprint(),
+        io_error("oh yes!").fingerprint()
+    );
+}
+
+#[test]
+fn test_builder() {
+    let a = anyhow!("oh no!");
+    let b = anyhow!(String::from("oh no!"));
+    let c = anyhow!("oh yes!");
+
+    let builder = Fingerprint::builder().type_names(false).clone();
+    assert_eq!(builder.fingerprint(&a), builder.fingerprint(&b));
+    assert_ne!(builder.fingerprint(&a), builder.fingerprint(&c));
+
+    let builder = Fingerprint::builder().messages(false).clone();
+    assert_ne!(builder.fingerprint(&a), builder.fingerprint(&b));
+    assert_eq!(builder.fingerprint(&a), builder.fingerprint(&c));
+}
+
+#[test]
+fn test_frames() {
+    fn f() -> Error {
+        anyhow!("oh no!")
+    }
+
+    fn g() -> Error {
+        anyhow!("oh no!")
+    }
+
+    // Without a captured backtrace, frames don't contribute.
+    let builder = Fingerprint::builder().frames(1).clone();
+    let same = builder.fingerprint(&f()) == builder.fingerprint(&g());
+    let captured = format!("{:?}", f()).contains("test_frames::f");
+    assert_eq!(same, !captured);
+    assert_eq!(builder.fingerprint(&f()), builder.fingerprint(&f()));
+}
+
+#[test]
+fn test_display() {
+    let fingerprint = anyhow!("oh no!").fingerprint();
+    let display = fingerprint.to_string();
+    assert_eq!(16, display.len());
+    assert_eq!(
+        fingerprint.as_u64(),
+        u64::from_str_radix(&display, 16).unwrap()
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_remote() {
+    let error = io_error("oh no!");
+    let json = serde_json::to_string(&error).unwrap();
+    let remote: anyhow::RemoteError = serde_json::from_str(&json).unwrap();
+    let remote = Error::new(remote);
+    assert_eq!(error.fingerprint(), remote.fingerprint());
+
+    let builder = Fingerprint::builder().frames(3).clone();
+    assert_eq!(builder.fingerprint(&error), builder.fingerprint(&remote));
+}
@@ -0,0 +1,93 @@
+#![cfg(feature = "backtrace")]
+
+use anyhow::{anyhow, Backtrace, Error};
+
+const LINE: u32 = line!() + 3;
+
+fn f() -> Error {
+    anyhow!("oh no!")
+}
+
+#[inline(never)]
+fn g() -> Backtrace {
+    Backtrace::force_capture()
+}
+
+fn captured(error: &Error) -> bool {
+    format!("{:?}", error).contains("\n\nStack backtrace:\n")
+}
+
+#[test]
+fn test_frames() {
+    let error = f();
+    let frames: Vec<_> = error.frames().collect();
+    assert_eq!(captured(&error), !frames.is_empty());
+    assert_eq!(frames.len(), error.frames().len());
+    if frames.is_empty() {
+        return;
+    }
+
+    let (index, symbol) = frames
+        .iter()
+        .enumerate()
+        .flat_map(|(i, frame)| frame.symbols().iter().map(move |symbol| (i, symbol)))
+        .find(|(_, symbol)| {
+            symbol
+                .name()
+                .map_or(false, |name| name.ends_with("test_frames::f"))
+        })
+        .unwrap();
+    assert!(symbol.filename().unwrap().ends_with("tests/test_frames.rs"));
+    assert_eq!(Some(LINE), symbol.lineno());
+    assert!(symbol.colno().is_some());
+
+    // Innermost first: the caller comes after the function creating the error.
+    let caller = frames.iter().position(|frame| {
+        frame.symbols().iter().any(|symbol| {
+            symbol
+                .name()
+                .map_or(false, |name| name.ends_with("test_frames::test_frames"))
+        })
+    });
+    assert!(caller.unwrap() >= index);
+
+    for frame in &frames {
+        if let Some(base) = frame.module_base_address() {
+            assert!(frame.ip() as usize >= base as usize);
+        }
+    }
+}
+
+#[test]
+fn test_context() {
+    // Context layers report the frames of the error they wrap.
+    let error = f();
+    let expected: Vec<_> = error.frames().map(|frame| frame.ip()).collect();
+    let error = error.context("f failed");
+    let frames: Vec<_> = error.frames().map(|frame| frame.ip()).collect();
+    assert_eq!(expected, frames);
+}
+
+#[test]
+fn test_force_capture() {
+    // The backtrace starts at the function that captured it.
+    let backtrace = g();
+    let first = match backtrace.frames().next() {
+        Some(first) => first,
+        None => return,
+    };
+    assert!(first.symbols().iter().any(|symbol| {
+        symbol
+            .name()
+            .map_or(false, |name| name.ends_with("test_frames::g"))
+    }));
+}
+
+#[rustversion::since(1.65)]
+#[test]
+fn test_with_backtrace() {
+    // The frames of a standard library backtrace are not known.
+    let error = f().with_backtrace(std::backtrace::Backtrace::force_capture());
+    assert_eq!(0, error.frames().len());
+    assert_eq!(None, error.frames_backtrace().id());
+}
@@ -0,0 +1,95 @@
+#![cfg(feature = "std")]
+
+use anyhow::{anyhow, bail, Error, FutureExt, Result};
+use futures::task::noop_waker;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread;
+
+// Returns Pending on the first poll, like a future waiting on I/O.
+struct Yield(bool);
+
+impl Future for Yield {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.0 {
+            Poll::Ready(())
+        } else {
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+fn poll<F: Future + Unpin>(future: &mut F) -> Poll<F::Output> {
+    let waker = noop_waker();
+    Pin::new(future).poll(&mut Context::from_waker(&waker))
+}
+
+fn ready<F: Future + Unpin>(mut future: F) -> F::Output {
+    loop {
+        if let Poll::Ready(output) = poll(&mut future) {
+            return output;
+        }
+    }
+}
+
+async fn fail() -> Result<()> {
+    bail!("oh no!");
+}
+
+async fn read() -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Other, "oh no!"))
+}
+
+#[test]
+fn test_context() {
+    let line = line!() + 1;
+    let error = ready(Box::pin(fail().context("failed to charge"))).unwrap_err();
+    assert_eq!("failed to charge: oh no!", format!("{:#}", error));
+    let location = error.locations().next().unwrap();
+    assert_eq!(line, location.line());
+    assert!(location.file().ends_with("test_future.rs"));
+
+    let error = ready(Box::pin(read().context("failed to read"))).unwrap_err();
+    assert_eq!("failed to read: oh no!", format!("{:#}", error));
+    assert!(error.root_cause().is::<io::Error>());
+}
+
+#[test]
+fn test_with_context() {
+    let ok = async { Ok::<_, Error>(1) }.with_context(|| -> &str { panic!("evaluated") });
+    assert_eq!(1, ready(Box::pin(ok)).unwrap());
+
+    let error = ready(Box::pin(
+        read().with_context(|| format!("failed to read {}", 1)),
+    ));
+    assert_eq!(
+        "failed to read 1: oh no!",
+        format!("{:#}", error.unwrap_err())
+    );
+}
+
+#[test]
+fn test_in_error_scope() {
+    let task = async {
+        Yield(false).await;
+        Err::<(), _>(anyhow!("oh no!"))
+    };
+    let mut task = Box::pin(task.in_error_scope(|| "processing order 7"));
+    assert!(poll(&mut task).is_pending());
+
+    // Errors created on this thread between polls are not in the scope.
+    assert_eq!("oh no!", format!("{:#}", anyhow!("oh no!")));
+
+    // The rest of the task runs on another thread.
+    let error = thread::spawn(move || ready(task)).join().unwrap();
+    assert_eq!(
+        "processing order 7: oh no!",
+        format!("{:#}", error.unwrap_err())
+    );
+}
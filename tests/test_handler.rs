@@ -0,0 +1,80 @@
+#![cfg(feature = "std")]
+
+use anyhow::{anyhow, DefaultHandler, Error, ReportHandler};
+use std::fmt;
+
+struct OneLine;
+
+impl ReportHandler for OneLine {
+    fn debug(&self, error: &Error, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "error: {:#}", error)
+    }
+}
+
+struct Causes;
+
+impl ReportHandler for Causes {
+    fn debug(&self, error: &Error, f: &mut fmt::Formatter) -> fmt::Result {
+        for (n, cause) in error.chain().enumerate() {
+            if n > 0 {
+                f.write_str(" <- ")?;
+            }
+            write!(f, "{}", cause)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct RequestId(u64);
+
+struct WithRequestId;
+
+impl ReportHandler for WithRequestId {
+    fn debug(&self, error: &Error, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(id) = error.request_ref::<RequestId>() {
+            write!(f, "[request {}] ", id.0)?;
+        }
+        DefaultHandler.debug(error, f)
+    }
+}
+
+#[test]
+fn test_with_handler() {
+    let error = anyhow!("oh no!").context("f failed").with_handler(OneLine);
+    assert_eq!("error: f failed: oh no!", format!("{:?}", error));
+    assert_eq!("f failed", error.to_string());
+
+    // Kept when context is added.
+    let error = error.context("g failed");
+    assert_eq!("error: g failed: f failed: oh no!", format!("{:?}", error));
+
+    // The outermost handler wins.
+    let error = error.with_handler(Causes);
+    assert_eq!("g failed <- f failed <- oh no!", format!("{:?}", error));
+}
+
+#[test]
+fn test_default_handler() {
+    let error = anyhow!("oh no!")
+        .attach(RequestId(7))
+        .context("f failed")
+        .with_handler(WithRequestId);
+    let debug = format!("{:?}", error);
+    assert!(debug.starts_with("[request 7] f failed\n"), "{}", debug);
+    assert!(debug.contains("\n\nCaused by:\n    oh no!"), "{}", debug);
+}
+
+// The global handler is installed once for the whole process, so everything
+// about it is checked in one test.
+#[test]
+fn test_set_handler() {
+    anyhow::set_handler(Causes).unwrap();
+    assert!(anyhow::set_handler(OneLine).is_err());
+
+    let error = anyhow!("oh no!").context("f failed");
+    assert_eq!("f failed <- oh no!", format!("{:?}", error));
+
+    let error = error.with_handler(OneLine);
+    assert_eq!("error: f failed: oh no!", format!("{:?}", error));
+}
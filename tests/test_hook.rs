@@ -0,0 +1,71 @@
+#![cfg(feature = "std")]
+
+use anyhow::{anyhow, Context, Error, Result};
+use std::cell::RefCell;
+use std::io;
+
+thread_local! {
+    static CREATED: RefCell<Vec<String>> = RefCell::new(Vec::new());
+}
+
+fn hook(error: &Error) {
+    CREATED.with(|created| created.borrow_mut().push(format!("{:#}", error)));
+
+    // Does not recurse into the hook.
+    let _ = anyhow!("created by the hook").context("in the hook");
+}
+
+fn created() -> usize {
+    CREATED.with(|created| created.borrow().len())
+}
+
+fn last() -> String {
+    CREATED.with(|created| created.borrow().last().unwrap().clone())
+}
+
+fn io_error() -> Result<()> {
+    Err(io::Error::new(io::ErrorKind::Other, "oh no!"))?;
+    unreachable!()
+}
+
+// The hook is global to the process, so everything is checked in one test.
+#[test]
+fn test_hook() {
+    anyhow::set_hook(hook).unwrap();
+    assert!(anyhow::set_hook(hook).is_err());
+
+    let error = anyhow!("oh no!");
+    assert_eq!(1, created());
+    assert_eq!("oh no!", last());
+
+    let _ = error.context("f failed");
+    assert_eq!(2, created());
+    assert_eq!("f failed: oh no!", last());
+
+    let error = io_error().context("g failed").unwrap_err();
+    assert_eq!(4, created());
+    assert_eq!("g failed: oh no!", last());
+
+    let _ = Err::<(), _>(io::Error::new(io::ErrorKind::Other, "oh no!"))
+        .with_context(|| "h failed")
+        .unwrap_err();
+    assert_eq!(5, created());
+    assert_eq!("h failed: oh no!", last());
+
+    // Once per error, however many scopes it is created in, and with the
+    // layers of the scopes already in place.
+    {
+        let _outer = anyhow::scope(|| "outer");
+        let _inner = anyhow::scope(|| "inner");
+        let _ = anyhow!("oh no!");
+    }
+    assert_eq!(6, created());
+    assert_eq!("outer: inner: oh no!", last());
+
+    // Sharing an error and taking it back does not create a new error.
+    let shared = error.into_shared();
+    let clone = shared.clone();
+    let _ = Error::from(shared);
+    drop(clone);
+    assert_eq!(6, created());
+}
@@ -0,0 +1,64 @@
+#![cfg(feature = "backtrace")]
+
+use anyhow::{anyhow, Backtrace, Error};
+
+fn f() -> Error {
+    anyhow!("oh no!")
+}
+
+#[inline(never)]
+fn capture() -> Backtrace {
+    Backtrace::force_capture()
+}
+
+#[test]
+fn test_same_frames() {
+    let mut id = None;
+    for round in 0..2 {
+        let backtraces: Vec<Backtrace> = (0..3).map(|_| Backtrace::force_capture()).collect();
+        if round == 0 {
+            id = backtraces[0].id();
+            assert!(backtraces[0].is_first_occurrence());
+        }
+        assert!(id.is_some());
+        assert!(backtraces.iter().all(|backtrace| backtrace.id() == id));
+        assert!(!backtraces[1].is_first_occurrence());
+        assert!(!backtraces[2].is_first_occurrence());
+
+        // The symbols are resolved once, and shared.
+        let first = backtraces[0].frames().next().unwrap();
+        let second = backtraces[1].frames().next().unwrap();
+        assert!(std::ptr::eq(first, second));
+
+        // In the second round, the id stays the same after the earlier
+        // backtraces are gone.
+        drop(backtraces);
+    }
+    assert!(Backtrace::force_capture().id().is_some());
+    assert_eq!(16, id.unwrap().to_string().len());
+}
+
+#[test]
+fn test_different_frames() {
+    let here = capture();
+    let there = capture();
+    assert!(here.id().is_some());
+    assert_ne!(here.id(), there.id());
+    assert!(here.is_first_occurrence());
+    assert!(there.is_first_occurrence());
+}
+
+#[test]
+fn test_error() {
+    let errors: Vec<Error> = (0..2).map(|_| f()).collect();
+    match errors[0].frames_backtrace().id() {
+        Some(id) => assert_eq!(Some(id), errors[1].frames_backtrace().id()),
+        None => return,
+    }
+    assert!(errors[0].frames_backtrace().is_first_occurrence());
+    assert!(!errors[1].frames_backtrace().is_first_occurrence());
+
+    let disabled = Error::msg_without_backtrace("oh no!");
+    assert_eq!(None, disabled.frames_backtrace().id());
+    assert!(!disabled.frames_backtrace().is_first_occurrence());
+}
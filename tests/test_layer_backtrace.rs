@@ -0,0 +1,69 @@
+#![cfg(feature = "std")]
+
+use anyhow::{anyhow, Context, Error, Result};
+
+fn load() -> Result<()> {
+    Err(anyhow!("file not found")).context("failed to load config")
+}
+
+fn caused_by(error: &Error) -> String {
+    let debug = format!("{:?}", error);
+    let end = debug.find("\n\nStack backtrace:\n").unwrap_or(debug.len());
+    debug[..end].to_owned()
+}
+
+// Whether a backtrace captured here shows this function, which needs debug
+// information.
+#[rustversion::since(1.65)]
+#[inline(never)]
+fn resolvable() -> bool {
+    let backtrace = std::backtrace::Backtrace::force_capture();
+    backtrace
+        .to_string()
+        .contains("test_layer_backtrace::resolvable")
+}
+
+#[rustversion::before(1.65)]
+fn resolvable() -> bool {
+    false
+}
+
+// One test, as the setting is global.
+#[test]
+fn test_layer_backtrace() {
+    let without = caused_by(&load().unwrap_err());
+    assert!(
+        !without.contains(": test_layer_backtrace::load"),
+        "{}",
+        without
+    );
+
+    anyhow::set_context_backtraces(2);
+    let error = load().unwrap_err();
+    let debug = caused_by(&error);
+    let head = &debug[..debug.find("\n\nCaused by:").unwrap()];
+    let frames: Vec<_> = head.lines().filter(|line| line.contains(": ")).collect();
+    assert!(frames.len() <= 2, "{}", debug);
+    if resolvable() {
+        assert!(!frames.is_empty(), "{}", debug);
+    }
+    if let Some(first) = frames.first() {
+        assert!(first.ends_with(": test_layer_backtrace::load"), "{}", debug);
+        assert!(!head.contains("note: "), "{}", debug);
+    }
+
+    // Layers of context added to an anyhow::Error show theirs too.
+    let error = error.context("failed to start");
+    let debug = caused_by(&error);
+    let causes = &debug[debug.find("\n\nCaused by:").unwrap()..];
+    assert_eq!(
+        frames.is_empty(),
+        !causes.contains(": test_layer_backtrace::load"),
+        "{}",
+        debug
+    );
+
+    anyhow::set_context_backtraces(0);
+    let error = load().unwrap_err();
+    assert_eq!(without, caused_by(&error));
+}
@@ -0,0 +1,126 @@
+#![cfg(feature = "std")]
+
+use anyhow::{anyhow, bail, Context, Error, Result, SharedError};
+use std::io;
+
+fn io_error() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "oh no!")
+}
+
+#[test]
+fn test_macro() {
+    let line = line!() + 1;
+    let error = anyhow!("oh no!");
+    assert_eq!(file!(), error.location().file());
+    assert_eq!(line, error.location().line());
+}
+
+#[test]
+fn test_bail() {
+    const LINE: u32 = line!() + 2;
+    fn f() -> Result<()> {
+        bail!("oh no!");
+    }
+    let error = f().unwrap_err();
+    assert_eq!(LINE, error.location().line());
+}
+
+#[test]
+fn test_new() {
+    let line = line!() + 1;
+    let error = Error::new(io_error());
+    assert_eq!(line, error.location().line());
+}
+
+#[test]
+fn test_question_mark() {
+    const LINE: u32 = line!() + 2;
+    fn f() -> Result<()> {
+        Err(io_error())?;
+        Ok(())
+    }
+    let error = f().unwrap_err();
+    assert_eq!(LINE, error.location().line());
+}
+
+#[test]
+fn test_context() {
+    let origin = line!() + 1;
+    let error = anyhow!("oh no!");
+    let context = line!() + 1;
+    let error = error.context("f failed");
+    let result = Err::<(), _>(error).context("g failed");
+    let outer = line!() - 1;
+
+    let error = result.unwrap_err();
+    assert_eq!(origin, error.location().line());
+    let lines: Vec<u32> = error.locations().map(|location| location.line()).collect();
+    assert_eq!(vec![outer, context, origin], lines);
+}
+
+#[test]
+fn test_std_context() {
+    let line = line!() + 1;
+    let error = Err::<(), _>(io_error()).context("f failed").unwrap_err();
+    assert_eq!(line, error.location().line());
+    assert_eq!(1, error.locations().count());
+
+    let line = line!() + 1;
+    let error = None::<()>.context("not found").unwrap_err();
+    assert_eq!(line, error.location().line());
+}
+
+#[test]
+fn test_shared() {
+    let line = line!() + 1;
+    let error = anyhow!("oh no!");
+    let shared = SharedError::from(error);
+    let _clone = shared.clone();
+
+    let error = Error::from(shared);
+    assert_eq!(line, error.location().line());
+    assert_eq!(1, error.locations().count());
+}
+
+#[test]
+fn test_debug() {
+    anyhow::set_debug_locations(true);
+    let line = line!() + 1;
+    let error = anyhow!("oh no!");
+    let debug = format!("{:?}", error);
+    let expected = format!("oh no!\n    at {}:{}:17", file!(), line);
+    assert!(debug.starts_with(&expected), "{}", debug);
+}
+
+#[test]
+fn test_debug_causes() {
+    anyhow::set_debug_locations(true);
+    let origin = line!() + 1;
+    let error = anyhow!("oh no!");
+    let context = line!() + 1;
+    let error = error.context("f failed");
+    let debug = format!("{:?}", error);
+    let expected = format!(
+        "f failed\n    at {file}:{}:23\n\nCaused by:\n    oh no!\n    at {file}:{}:17",
+        context,
+        origin,
+        file = file!(),
+    );
+    assert!(debug.starts_with(&expected), "{}", debug);
+}
+
+#[test]
+fn test_source_at_same_address() {
+    #[derive(thiserror::Error, Debug)]
+    #[error("outer")]
+    struct Outer(#[source] Inner);
+
+    #[derive(thiserror::Error, Debug)]
+    #[error("inner")]
+    struct Inner;
+
+    anyhow::set_debug_locations(true);
+    let error = Error::new(Outer(Inner));
+    let debug = format!("{:?}", error);
+    assert_eq!(1, debug.matches("\n    at ").count(), "{}", debug);
+}
@@ -0,0 +1,98 @@
+#![cfg(feature = "std")]
+
+use anyhow::{anyhow, bail, Error, PanicError, Result};
+use std::panic;
+use std::thread;
+
+#[test]
+fn test_ok() {
+    let result = anyhow::catch_unwind(|| Ok(1));
+    assert_eq!(1, result.unwrap());
+}
+
+#[test]
+fn test_returned_error() {
+    let error = anyhow::catch_unwind(|| -> Result<()> { bail!("oh no!") }).unwrap_err();
+    assert!(!error.is::<PanicError>());
+    assert_eq!("oh no!", error.to_string());
+}
+
+#[test]
+fn test_panic() {
+    let line = line!() + 2;
+    let error = anyhow::catch_unwind(|| -> Result<()> {
+        panic!("oh no!");
+    })
+    .unwrap_err();
+
+    let panic = error.downcast_ref::<PanicError>().unwrap();
+    assert_eq!("oh no!", panic.message());
+    let location = format!("{}:{}:9", file!(), line);
+    assert_eq!(Some(location.as_str()), panic.location());
+
+    anyhow::set_debug_locations(true);
+    let debug = format!("{:?}", error);
+    let expected = format!("oh no!\n    at {}", location);
+    assert!(debug.starts_with(&expected), "{}", debug);
+}
+
+#[test]
+fn test_formatted_message() {
+    let error = anyhow::catch_unwind(|| -> Result<()> {
+        panic!("job {} crashed", 7);
+    })
+    .unwrap_err();
+    let panic = error.downcast_ref::<PanicError>().unwrap();
+    assert_eq!("job 7 crashed", panic.message());
+    assert!(panic.location().is_some());
+}
+
+#[test]
+fn test_nested() {
+    let error = anyhow::catch_unwind(|| -> Result<()> {
+        let inner = anyhow::catch_unwind(|| -> Result<()> { panic!("inner") });
+        assert!(inner.unwrap_err().is::<PanicError>());
+        panic!("outer");
+    })
+    .unwrap_err();
+    let panic = error.downcast_ref::<PanicError>().unwrap();
+    assert_eq!("outer", panic.message());
+    assert!(panic.location().is_some());
+}
+
+#[test]
+fn test_from_panic() {
+    let payload = thread::spawn(|| panic!("oh no!")).join().unwrap_err();
+    let error = Error::from_panic(payload);
+    let panic = error.downcast_ref::<PanicError>().unwrap();
+    assert_eq!("oh no!", panic.message());
+    assert_eq!(None, panic.location());
+
+    let payload = panic::catch_unwind(|| panic::panic_any(1)).unwrap_err();
+    let error = Error::from_panic(payload);
+    assert_eq!("Box<dyn Any>", error.to_string());
+}
+
+#[test]
+fn test_flatten() {
+    let error = anyhow::catch_unwind(|| -> Result<()> {
+        panic::panic_any(anyhow!("oh no!").context("f failed"));
+    })
+    .unwrap_err();
+    assert!(!error.is::<PanicError>());
+    assert_eq!("f failed: oh no!", format!("{:#}", error));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serialize() {
+    let line = line!() + 2;
+    let error = anyhow::catch_unwind(|| -> Result<()> {
+        panic!("oh no!");
+    })
+    .unwrap_err();
+    let value = serde_json::to_value(&error).unwrap();
+    assert_eq!("oh no!", value["message"]);
+    assert_eq!("anyhow::PanicError", value["type_name"]);
+    assert_eq!(format!("{}:{}:9", file!(), line), value["location"]);
+}
@@ -0,0 +1,44 @@
+#![cfg(feature = "std")]
+
+use anyhow::{anyhow, BacktracePolicy, Error};
+use std::env;
+use std::io;
+
+fn captured(error: &Error) -> bool {
+    format!("{:?}", error).contains("\n\nStack backtrace:\n")
+}
+
+// A single test, since the policy applies to the whole process.
+#[test]
+fn test_policy() {
+    env::set_var("RUST_LIB_BACKTRACE", "1");
+    if !captured(&anyhow!("oh no!")) {
+        // No backtrace support on this compiler.
+        return;
+    }
+
+    assert!(!captured(&Error::msg_without_backtrace("oh no!")));
+    let error = io::Error::new(io::ErrorKind::Other, "oh no!");
+    assert!(!captured(&Error::new_without_backtrace(error)));
+
+    anyhow::set_backtrace_policy(BacktracePolicy::Never);
+    assert!(!captured(&anyhow!("oh no!")));
+    let error = Err::<(), _>(io::Error::new(io::ErrorKind::Other, "oh no!"));
+    assert!(!captured(
+        &anyhow::Context::context(error, "f failed").unwrap_err()
+    ));
+
+    anyhow::set_backtrace_policy(BacktracePolicy::OncePerCallSite);
+    let errors: Vec<_> = (0..3).map(|_| captured(&anyhow!("oh no!"))).collect();
+    assert_eq!([true, false, false], *errors);
+    assert!(captured(&anyhow!("elsewhere")));
+
+    // The count may straddle two seconds.
+    anyhow::set_backtrace_policy(BacktracePolicy::Sampled(2));
+    let count = (0..10).filter(|_| captured(&anyhow!("oh no!"))).count();
+    assert!((1..=4).contains(&count), "{}", count);
+
+    anyhow::set_backtrace_policy(BacktracePolicy::Always);
+    assert!(captured(&anyhow!("oh no!")));
+    assert!(!captured(&Error::msg_without_backtrace("oh no!")));
+}
@@ -0,0 +1,68 @@
+#![cfg(error_generic_member_access)]
+#![cfg_attr(error_generic_member_access, feature(error_generic_member_access))]
+
+use anyhow::{Context, Error};
+use std::backtrace::Backtrace;
+use std::error::{self, Request};
+use std::fmt::{self, Display};
+
+#[derive(Debug, PartialEq)]
+struct StatusCode(u16);
+
+#[derive(Debug)]
+struct MyError {
+    backtrace: Backtrace,
+}
+
+impl Display for MyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("oh no!")
+    }
+}
+
+impl error::Error for MyError {
+    fn provide<'a>(&'a self, request: &mut Request<'a>) {
+        request
+            .provide_ref(&self.backtrace)
+            .provide_value(StatusCode(404));
+    }
+}
+
+fn error() -> Error {
+    Err::<(), _>(MyError {
+        backtrace: Backtrace::force_capture(),
+    })
+    .context("f failed")
+    .context(String::from("g failed"))
+    .unwrap_err()
+}
+
+#[test]
+fn test_backtrace() {
+    let error = error();
+    let inner = &error
+        .root_cause()
+        .downcast_ref::<MyError>()
+        .unwrap()
+        .backtrace;
+    assert!(std::ptr::eq(inner, error.backtrace()));
+
+    let provided = error::request_ref::<Backtrace>(&*error).unwrap();
+    assert!(std::ptr::eq(inner, provided));
+}
+
+#[test]
+fn test_value() {
+    let error = error();
+    let provided = error::request_value::<StatusCode>(&*error);
+    assert_eq!(Some(StatusCode(404)), provided);
+}
+
+#[test]
+fn test_own_backtrace() {
+    let error = anyhow::anyhow!("oh no!");
+    let backtrace = error.backtrace() as *const Backtrace;
+    let boxed: Box<dyn error::Error + Send + Sync> = error.into();
+    let provided = error::request_ref::<Backtrace>(&*boxed).unwrap();
+    assert!(std::ptr::eq(backtrace, provided));
+}
@@ -0,0 +1,115 @@
+#![cfg(feature = "serde")]
+
+use anyhow::{anyhow, Context, Error, RemoteError};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+#[error("oh no!")]
+struct MyError;
+
+#[derive(Error, Debug)]
+#[error("outer")]
+struct Outer(#[source] MyError);
+
+fn error() -> Error {
+    Err::<(), _>(Outer(MyError))
+        .context("f failed")
+        .context(String::from("g failed"))
+        .unwrap_err()
+}
+
+fn roundtrip(error: &Error) -> RemoteError {
+    let json = serde_json::to_string(error).unwrap();
+    serde_json::from_str(&json).unwrap()
+}
+
+#[test]
+fn test_display() {
+    let origin = error();
+    let remote = Error::new(roundtrip(&origin));
+    assert_eq!(origin.to_string(), remote.to_string());
+    assert_eq!(format!("{:#}", origin), format!("{:#}", remote));
+}
+
+#[test]
+fn test_debug() {
+    let origin = error();
+    let remote = Error::new(roundtrip(&origin));
+    assert_eq!(format!("{:?}", origin), format!("{:?}", remote));
+}
+
+#[test]
+fn test_chain() {
+    let origin = error();
+    let remote = Error::new(roundtrip(&origin));
+    let origin: Vec<String> = origin.chain().map(ToString::to_string).collect();
+    let remote: Vec<String> = remote.chain().map(ToString::to_string).collect();
+    assert_eq!(origin, remote);
+}
+
+#[test]
+fn test_type_name() {
+    let remote = roundtrip(&error());
+    assert!(remote.is::<String>());
+
+    let mut chain = anyhow::Chain::new(&remote).map(|cause| {
+        let cause = cause.downcast_ref::<RemoteError>().unwrap();
+        cause.type_name()
+    });
+    assert_eq!(Some(Some("alloc::string::String")), chain.next());
+    assert_eq!(Some(Some("&str")), chain.next());
+    assert_eq!(Some(Some("test_remote::Outer")), chain.next());
+    assert_eq!(Some(None), chain.next());
+    assert_eq!(None, chain.next());
+}
+
+#[test]
+fn test_location() {
+    let line = line!() + 1;
+    let origin = anyhow!("oh no!");
+    let remote = roundtrip(&origin);
+    let expected = format!("{}:{}:18", file!(), line);
+    assert_eq!(Some(expected.as_str()), remote.location());
+}
+
+#[test]
+fn test_serialize() {
+    // Serializing the rebuilt error again gives back the same report.
+    let origin = serde_json::to_value(error()).unwrap();
+    let remote = Error::new(roundtrip(&error()));
+    let mut remote = serde_json::to_value(remote).unwrap();
+    remote["backtrace"] = origin["backtrace"].clone();
+    assert_eq!(origin, remote);
+}
+
+#[test]
+fn test_backtrace() {
+    let json = r#"{
+        "message": "oh no!",
+        "backtrace": [
+            { "index": 0, "function": "main::f", "file": "src/main.rs", "line": 3, "column": 5 },
+            { "index": 0, "function": "main::g", "file": "src/main.rs", "line": 7, "column": 5 },
+            { "index": 1, "function": "main::main", "file": null, "line": null, "column": null }
+        ]
+    }"#;
+    let remote: RemoteError = serde_json::from_str(json).unwrap();
+    let expected = "   0: main::f\n             at src/main.rs:3:5\n      main::g\n             at src/main.rs:7:5\n   1: main::main";
+    assert_eq!(Some(expected), remote.backtrace());
+    assert!(remote.location().is_none());
+
+    let debug = format!("{:?}", Error::new(remote));
+    assert_eq!(format!("oh no!\n\nStack backtrace:\n{}", expected), debug);
+
+    let json = r#"{ "message": "oh no!", "backtrace": "   0: main::main" }"#;
+    let remote: RemoteError = serde_json::from_str(json).unwrap();
+    assert_eq!(Some("   0: main::main"), remote.backtrace());
+}
+
+#[test]
+fn test_missing_message() {
+    let error = serde_json::from_str::<RemoteError>(r#"{ "causes": [] }"#).unwrap_err();
+    assert_eq!(
+        "missing field `message` at line 1 column 16",
+        error.to_string()
+    );
+}
@@ -0,0 +1,191 @@
+#![cfg(feature = "std")]
+
+use anyhow::{anyhow, bail, Context, Result, Severity};
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+fn fail() -> Result<()> {
+    bail!("oh no!");
+}
+
+fn read() -> Result<()> {
+    Err(io::Error::new(io::ErrorKind::Other, "oh no!"))?;
+    Ok(())
+}
+
+#[test]
+fn test_scope() {
+    let error = {
+        let _scope = anyhow::scope(|| "processing order 7");
+        fail().unwrap_err()
+    };
+    assert_eq!("processing order 7: oh no!", format!("{:#}", error));
+    assert_eq!("processing order 7", error.to_string());
+    assert_eq!("oh no!", error.root_cause().to_string());
+
+    // Not applied once the guard is dropped.
+    assert_eq!("oh no!", format!("{:#}", fail().unwrap_err()));
+}
+
+#[test]
+fn test_nested() {
+    let _outer = anyhow::scope(|| "outer");
+    let error = {
+        let _inner = anyhow::scope(|| format!("inner {}", 1));
+        read().context("failed to read").unwrap_err()
+    };
+    // The scopes stay outermost, above context added inside them.
+    assert_eq!(
+        "outer: inner 1: failed to read: oh no!",
+        format!("{:#}", error),
+    );
+    assert!(error.root_cause().is::<io::Error>());
+
+    // Context added to an error does not repeat the scopes.
+    let error = anyhow!("oh no!").context("while testing");
+    assert_eq!("outer: while testing: oh no!", format!("{:#}", error));
+}
+
+#[test]
+fn test_outermost() {
+    fn charge() -> Result<()> {
+        bail!("nothing to charge");
+    }
+
+    fn process() -> Result<()> {
+        charge().context("failed to charge")?;
+        Ok(())
+    }
+
+    let error = {
+        let _scope = anyhow::scope(|| "processing order 7");
+        process().context("failed to process").unwrap_err()
+    };
+    assert_eq!(
+        "processing order 7: failed to process: failed to charge: nothing to charge",
+        format!("{:#}", error),
+    );
+    assert_eq!(4, error.chain().count());
+
+    // Context added after the scope has ended goes outside of it.
+    let error = error.context("while testing");
+    assert_eq!(
+        "while testing: processing order 7: failed to process: failed to charge: nothing to charge",
+        format!("{:#}", error),
+    );
+}
+
+#[test]
+fn test_fields() {
+    let error = {
+        let _scope = anyhow::scope(|| "processing order 7");
+        fail()
+            .unwrap_err()
+            .with_field("id", 7)
+            .with_field("amount", 0)
+    };
+    assert_eq!(3, error.chain().count());
+    assert_eq!("processing order 7", error.to_string());
+    let fields: Vec<String> = error.fields().map(|field| field.to_string()).collect();
+    assert_eq!(fields, ["amount=0", "id=7"]);
+}
+
+#[test]
+fn test_attachments() {
+    let error = {
+        let _scope = anyhow::scope(|| "processing order 7");
+        fail()
+            .unwrap_err()
+            .with_severity(Severity::Warning)
+            .context("failed to process")
+    };
+    // The scope layer is taken off and put back with its attachments.
+    assert_eq!(
+        "processing order 7: failed to process: oh no!",
+        format!("{:#}", error),
+    );
+    assert_eq!(Some(Severity::Warning), error.severity());
+}
+
+#[test]
+fn test_converted() {
+    // An error of another type created before the scope gets the scope when
+    // `?` converts it inside the scope.
+    let error = io::Error::new(io::ErrorKind::Other, "oh no!");
+    let convert = || -> Result<()> {
+        let _scope = anyhow::scope(|| "processing order 7");
+        Err(error)?;
+        Ok(())
+    };
+    let error = convert().unwrap_err();
+    assert_eq!("processing order 7: oh no!", format!("{:#}", error));
+
+    // An anyhow::Error created before the scope gets the scope when context
+    // is added to it inside the scope.
+    let error = anyhow!("oh no!");
+    let propagate = || -> Result<()> {
+        let _scope = anyhow::scope(|| "processing order 7");
+        Err(error).context("failed to propagate")?;
+        Ok(())
+    };
+    assert_eq!(
+        "processing order 7: failed to propagate: oh no!",
+        format!("{:#}", propagate().unwrap_err()),
+    );
+
+    // By `?` alone it passes through as is.
+    let error = anyhow!("oh no!");
+    let propagate = || -> Result<()> {
+        let _scope = anyhow::scope(|| "processing order 7");
+        Err(error)?;
+        Ok(())
+    };
+    assert_eq!("oh no!", format!("{:#}", propagate().unwrap_err()));
+}
+
+#[test]
+fn test_lazy() {
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    let scope = || {
+        anyhow::scope(|| {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+            "processing order 7"
+        })
+    };
+
+    // Not called while no error is created.
+    drop(scope());
+    assert_eq!(0, CALLS.load(Ordering::Relaxed));
+
+    // Called once for any number of errors.
+    let guard = scope();
+    let first = fail().unwrap_err();
+    let second = fail().unwrap_err();
+    drop(guard);
+    assert_eq!(1, CALLS.load(Ordering::Relaxed));
+    assert_eq!("processing order 7: oh no!", format!("{:#}", first));
+    assert_eq!("processing order 7: oh no!", format!("{:#}", second));
+}
+
+#[test]
+fn test_debug() {
+    anyhow::set_debug_locations(true);
+    let line = line!() + 1;
+    let scope = anyhow::scope(|| "processing order 7");
+    let error = fail().unwrap_err();
+    drop(scope);
+    let debug = format!("{:?}", error);
+    let expected = format!(
+        "processing order 7\n    at tests/test_scope.rs:{}:17\n\nCaused by:\n    oh no!\n",
+        line,
+    );
+    assert!(debug.starts_with(&expected), "{}", debug);
+}
+
+#[test]
+fn test_thread() {
+    let _scope = anyhow::scope(|| "on the main thread");
+    let error = std::thread::spawn(|| fail().unwrap_err()).join().unwrap();
+    assert_eq!("oh no!", format!("{:#}", error));
+}
@@ -0,0 +1,118 @@
+#![cfg(feature = "serde")]
+
+use anyhow::{anyhow, Context};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+#[error("oh no!")]
+struct MyError;
+
+fn error() -> anyhow::Error {
+    Err::<(), _>(MyError)
+        .context("f failed")
+        .context(String::from("g failed"))
+        .unwrap_err()
+}
+
+fn to_value(error: &anyhow::Error) -> Value {
+    let mut value = serde_json::to_value(error).unwrap();
+    // The backtrace and locations depend on the environment and on this file,
+    // and are checked separately.
+    let object = value.as_object_mut().unwrap();
+    object.remove("backtrace");
+    object.remove("location");
+    for cause in object["causes"].as_array_mut().unwrap() {
+        cause.as_object_mut().unwrap().remove("location");
+    }
+    value
+}
+
+#[test]
+fn test_error() {
+    let expected = json!({
+        "message": "g failed",
+        "type_name": "alloc::string::String",
+        "causes": [
+            { "message": "f failed", "type_name": "&str" },
+            { "message": "oh no!", "type_name": "test_serde::MyError" },
+        ],
+    });
+    assert_eq!(expected, to_value(&error()));
+}
+
+#[test]
+fn test_new() {
+    let error = anyhow::Error::new(MyError);
+    let expected = json!({
+        "message": "oh no!",
+        "type_name": "test_serde::MyError",
+        "causes": [],
+    });
+    assert_eq!(expected, to_value(&error));
+}
+
+#[test]
+fn test_chain() {
+    let origin = line!() + 1;
+    let error = anyhow!("oh no!");
+    let context = line!() + 1;
+    let error = error.context("f failed");
+
+    let location = |line, column| format!("{}:{}:{}", file!(), line, column);
+    let expected = json!([
+        { "message": "f failed", "type_name": "&str", "location": location(context, 23) },
+        { "message": "oh no!", "type_name": "&str", "location": location(origin, 17) },
+    ]);
+    assert_eq!(expected, serde_json::to_value(error.chain()).unwrap());
+
+    let mut chain = error.chain();
+    chain.next();
+    let expected = json!([
+        { "message": "oh no!", "type_name": "&str", "location": location(origin, 17) },
+    ]);
+    assert_eq!(expected, serde_json::to_value(chain).unwrap());
+}
+
+#[test]
+fn test_location() {
+    let line = line!() + 1;
+    let error = Err::<(), _>(MyError).context("f failed").unwrap_err();
+    let value = serde_json::to_value(&error).unwrap();
+    let expected = format!("{}:{}:39", file!(), line);
+    assert_eq!(expected, value["location"]);
+    assert_eq!(Value::Null, value["causes"][0]["location"]);
+}
+
+#[test]
+fn test_backtrace() {
+    let value = serde_json::to_value(anyhow!("oh no!")).unwrap();
+    match &value["backtrace"] {
+        Value::Null => {}
+        Value::String(text) => assert!(!cfg!(feature = "backtrace"), "{}", text),
+        Value::Array(frames) => {
+            assert!(cfg!(feature = "backtrace"));
+            assert!(!frames.is_empty());
+            for frame in frames {
+                assert!(frame["function"].is_string() || frame["function"].is_null());
+                assert!(frame["line"].is_null() || frame["line"].is_u64());
+            }
+        }
+        other => panic!("unexpected backtrace: {}", other),
+    }
+}
+
+#[test]
+fn test_source() {
+    #[derive(Error, Debug)]
+    #[error("outer")]
+    struct Outer(#[source] MyError);
+
+    let error = anyhow::Error::new(Outer(MyError));
+    let expected = json!({
+        "message": "outer",
+        "type_name": "test_serde::test_source::Outer",
+        "causes": [{ "message": "oh no!", "type_name": null }],
+    });
+    assert_eq!(expected, to_value(&error));
+}
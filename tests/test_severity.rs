@@ -0,0 +1,69 @@
+#![cfg(feature = "std")]
+
+use anyhow::{anyhow, Context, Error, Result, Severity};
+use std::io;
+
+fn read() -> Result<(), io::Error> {
+    Err(io::Error::new(io::ErrorKind::Other, "oh no!"))
+}
+
+#[test]
+fn test_severity() {
+    let error = anyhow!("oh no!");
+    assert_eq!(None, error.severity());
+
+    let error = error.with_severity(Severity::Critical);
+    assert_eq!(Some(Severity::Critical), error.severity());
+
+    // Context added on top does not lower it.
+    let error = error.context("f failed").with_severity(Severity::Info);
+    assert_eq!(Some(Severity::Critical), error.severity());
+    assert_eq!("f failed: oh no!", format!("{:#}", error));
+}
+
+#[test]
+fn test_context_severity() {
+    let error = read()
+        .context_severity(Severity::Warning, "failed to read")
+        .unwrap_err();
+    assert_eq!(Some(Severity::Warning), error.severity());
+    assert_eq!("failed to read: oh no!", format!("{:#}", error));
+    assert!(error.is::<io::Error>());
+
+    let error = None::<()>
+        .context_severity(Severity::Info, "no such user")
+        .unwrap_err();
+    assert_eq!(Some(Severity::Info), error.severity());
+    assert_eq!("no such user", error.to_string());
+}
+
+#[test]
+fn test_aggregate() {
+    let error = Error::aggregate(vec![
+        anyhow!("bad row").with_severity(Severity::Info),
+        anyhow!("disk full").with_severity(Severity::Critical),
+    ])
+    .context("import failed");
+    assert_eq!(Some(Severity::Critical), error.severity());
+}
+
+#[test]
+fn test_debug() {
+    let error = anyhow!("oh no!")
+        .with_severity(Severity::Error)
+        .context("f failed");
+    let debug = format!("{:?}", error);
+    assert!(debug.starts_with("[error] f failed\n"), "{}", debug);
+    assert!(debug.contains("\n\nCaused by:\n    oh no!"), "{}", debug);
+
+    let debug = format!("{:?}", anyhow!("oh no!"));
+    assert!(debug.starts_with("oh no!"), "{}", debug);
+}
+
+#[test]
+fn test_ord() {
+    assert!(Severity::Info < Severity::Warning);
+    assert!(Severity::Warning < Severity::Error);
+    assert!(Severity::Error < Severity::Critical);
+    assert_eq!("critical", Severity::Critical.to_string());
+}
@@ -0,0 +1,76 @@
+#![cfg(feature = "std")]
+
+mod drop;
+
+use self::drop::{DetectDrop, Flag};
+use anyhow::{anyhow, Context, Error, SharedError};
+use std::io;
+
+fn error() -> Error {
+    Err::<(), _>(io::Error::new(io::ErrorKind::Other, "oh no!"))
+        .context("f failed")
+        .context("g failed")
+        .unwrap_err()
+}
+
+#[test]
+fn test_autotraits() {
+    fn assert<E: Clone + Send + Sync + 'static>() {}
+    assert::<SharedError>();
+}
+
+#[test]
+fn test_accessors() {
+    let shared = error().into_shared();
+    let clone = shared.clone();
+    assert_eq!("g failed", clone.to_string());
+    assert_eq!("g failed: f failed: oh no!", format!("{:#}", clone));
+    assert!(clone.is::<io::Error>());
+    assert_eq!(3, clone.chain().count());
+    assert_eq!("oh no!", clone.root_cause().to_string());
+    assert_eq!("g failed", *shared.downcast_ref::<&str>().unwrap());
+}
+
+#[test]
+fn test_roundtrip_unique() {
+    let shared = error().into_shared();
+    let mut error = Error::from(shared);
+    assert!(error.downcast_mut::<io::Error>().is_some());
+    assert!(error.downcast::<io::Error>().is_ok());
+}
+
+#[test]
+fn test_roundtrip_shared() {
+    let shared = error().into_shared();
+    let mut error = Error::from(shared.clone());
+    assert_eq!(format!("{:?}", shared), format!("{:?}", error));
+    assert_eq!(format!("{:#?}", shared), format!("{:#?}", error));
+    assert_eq!(format!("{:#}", shared), format!("{:#}", error));
+    assert!(error.downcast_ref::<io::Error>().is_some());
+    assert!(error.downcast_mut::<io::Error>().is_none());
+    assert!(error.downcast_mut::<SharedError>().is_some());
+
+    let error = error.context("h failed");
+    assert!(error.is::<io::Error>());
+    let error = error.downcast::<io::Error>().unwrap_err();
+    assert!(error.downcast::<SharedError>().is_ok());
+}
+
+#[test]
+fn test_drop() {
+    let has_dropped = Flag::new();
+    let shared = Error::new(DetectDrop::new(&has_dropped)).into_shared();
+    let error = Error::from(shared.clone());
+    drop(shared);
+    assert!(!has_dropped.get());
+    drop(error);
+    assert!(has_dropped.get());
+}
+
+#[test]
+fn test_message() {
+    let shared = SharedError::from(anyhow!("oh no!"));
+    let error = Error::from(shared.clone());
+    assert_eq!(format!("{:?}", shared), format!("{:?}", error));
+    assert_eq!("oh no!", *shared.downcast_ref::<&str>().unwrap());
+}
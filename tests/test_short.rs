@@ -0,0 +1,128 @@
+#![cfg(feature = "std")]
+
+use anyhow::{anyhow, Error};
+use std::env;
+
+const COLLAPSED: &[&str] = &["tokio", "test_short::runtime"];
+
+// The short form is printed unless RUST_BACKTRACE=full, which this overrides.
+fn setup() {
+    env::set_var("RUST_LIB_BACKTRACE", "1");
+    anyhow::set_collapsed_crates(COLLAPSED);
+}
+
+fn backtrace(error: &Error) -> Option<String> {
+    let debug = format!("{:?}", error);
+    let start = debug.find("\n\nStack backtrace:\n")?;
+    Some(debug[start + "\n\nStack backtrace:\n".len()..].to_owned())
+}
+
+fn f() -> Error {
+    anyhow!("oh no!")
+}
+
+mod runtime {
+    use anyhow::Error;
+
+    #[inline(never)]
+    pub fn block_on(f: impl FnOnce() -> Error) -> Error {
+        enter(f)
+    }
+
+    #[inline(never)]
+    fn enter(f: impl FnOnce() -> Error) -> Error {
+        f()
+    }
+}
+
+#[test]
+fn test_short() {
+    setup();
+    let backtrace = match backtrace(&f()) {
+        Some(backtrace) => backtrace,
+        None => return,
+    };
+    let first = backtrace.lines().next().unwrap();
+    assert_eq!("   0: test_short::f", first, "{}", backtrace);
+    assert!(
+        backtrace.contains(": test_short::test_short\n"),
+        "{}",
+        backtrace
+    );
+    assert!(!backtrace.contains("anyhow::"), "{}", backtrace);
+    assert!(
+        !backtrace.contains("__rust_begin_short_backtrace"),
+        "{}",
+        backtrace
+    );
+    assert!(!backtrace.contains("core::ops::function"), "{}", backtrace);
+    assert!(
+        backtrace.ends_with("for a verbose backtrace."),
+        "{}",
+        backtrace
+    );
+}
+
+#[test]
+fn test_thread() {
+    setup();
+    let backtrace = match backtrace(&std::thread::spawn(f).join().unwrap()) {
+        Some(backtrace) => backtrace,
+        None => return,
+    };
+    assert!(!backtrace.contains("std::thread"), "{}", backtrace);
+}
+
+#[test]
+fn test_collapsed() {
+    setup();
+    let backtrace = match backtrace(&runtime::block_on(f)) {
+        Some(backtrace) => backtrace,
+        None => return,
+    };
+    let expected = "      [... 2 frames of test_short::runtime omitted]\n";
+    assert!(backtrace.contains(expected), "{}", backtrace);
+    assert!(!backtrace.contains("runtime::enter"), "{}", backtrace);
+}
+
+// The backtrace crate's backtraces print in short form by themselves.
+#[cfg(feature = "backtrace")]
+#[test]
+fn test_display() {
+    setup();
+    let error = f();
+    let backtrace = error.frames_backtrace();
+    if backtrace.id().is_none() {
+        return;
+    }
+    let short = backtrace.to_string();
+    assert!(short.contains("   0: test_short::f\n"), "{}", short);
+    assert!(!short.contains("anyhow::"), "{}", short);
+    assert!(!short.contains("__rust_begin_short_backtrace"), "{}", short);
+    let full = format!("{:#}", backtrace);
+    assert!(full.contains("__rust_begin_short_backtrace"), "{}", full);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_remote() {
+    setup();
+    let json = r#"{
+        "message": "oh no!",
+        "backtrace": [
+            { "index": 0, "function": "anyhow::error::<impl anyhow::Error>::msg", "file": null, "line": null, "column": null },
+            { "index": 1, "function": "core::result::Result<T,E>::map_err", "file": null, "line": null, "column": null },
+            { "index": 2, "function": "main::f", "file": "src/main.rs", "line": 3, "column": 5 },
+            { "index": 3, "function": "tokio::runtime::Runtime::block_on", "file": null, "line": null, "column": null },
+            { "index": 4, "function": "<tokio::task::Task as core::future::Future>::poll", "file": null, "line": null, "column": null },
+            { "index": 5, "function": "main::main", "file": "src/main.rs", "line": 9, "column": 5 },
+            { "index": 6, "function": "core::ops::function::FnOnce::call_once", "file": null, "line": null, "column": null },
+            { "index": 7, "function": "std::sys::backtrace::__rust_begin_short_backtrace", "file": null, "line": null, "column": null },
+            { "index": 8, "function": "std::rt::lang_start::{{closure}}", "file": null, "line": null, "column": null },
+            { "index": 9, "function": "main", "file": null, "line": null, "column": null }
+        ]
+    }"#;
+    let remote: anyhow::RemoteError = serde_json::from_str(json).unwrap();
+    let expected = "   0: main::f\n             at src/main.rs:3:5\n      [... 2 frames of tokio omitted]\n   3: main::main\n             at src/main.rs:9:5\nnote: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace.";
+    assert_eq!(Some(expected.to_owned()), backtrace(&Error::new(remote)));
+}
@@ -0,0 +1,74 @@
+#![cfg(feature = "backtrace")]
+
+use anyhow::{anyhow, BacktraceSymbol, UnresolvedBacktrace};
+use std::path::Path;
+
+#[test]
+fn test_roundtrip() {
+    let error = anyhow!("oh no!");
+    let backtrace = match error.unresolved_backtrace() {
+        Some(backtrace) => backtrace,
+        None => return,
+    };
+    assert_eq!(
+        backtrace.frames().len(),
+        error.frames().filter(|frame| !frame.ip().is_null()).count(),
+    );
+    let parsed: UnresolvedBacktrace = backtrace.to_string().parse().unwrap();
+    assert_eq!(backtrace, parsed);
+
+    if cfg!(target_os = "linux") {
+        let exe = std::env::current_exe().unwrap();
+        let module = backtrace.frames()[0].module().unwrap();
+        assert_eq!(exe.to_str(), Some(backtrace.modules()[module].path()));
+    }
+}
+
+#[test]
+fn test_symbolize() {
+    let text = "unresolved backtrace v1\n\
+                module 0badcafe /usr/bin/server\n\
+                module - /lib/libc.so.6\n\
+                frames 0+0x10 0+0x20 1+0x30 0x7f00\n";
+    let backtrace: UnresolvedBacktrace = text.parse().unwrap();
+    assert_eq!(text, backtrace.to_string());
+    assert_eq!(
+        Some(&[0x0b, 0xad, 0xca, 0xfe][..]),
+        backtrace.modules()[0].build_id(),
+    );
+    assert_eq!(None, backtrace.modules()[1].build_id());
+    assert_eq!(None, backtrace.frames()[3].module());
+    assert_eq!(0x7f00, backtrace.frames()[3].address());
+
+    let mut calls = Vec::new();
+    let printed = backtrace.symbolize(|module, address| {
+        calls.push((module.path().to_owned(), address));
+        let file = Path::new("src/main.rs");
+        match address {
+            0x10 => vec![
+                BacktraceSymbol::new(Some("server::read"), Some(file), Some(3), Some(5)),
+                BacktraceSymbol::new(Some("server::load"), Some(file), Some(8), None),
+            ],
+            0x20 => vec![BacktraceSymbol::new(Some("server::main"), None, None, None)],
+            _ => Vec::new(),
+        }
+    });
+    let expected = "   0: server::read\n             at src/main.rs:3:5\n      server::load\n             at src/main.rs:8\n   1: server::main\n   2: <unknown>\n   3: <unknown>\n";
+    assert_eq!(expected, printed);
+    assert_eq!(3, calls.len());
+    assert_eq!(("/lib/libc.so.6".to_owned(), 0x30), calls[2]);
+}
+
+#[test]
+fn test_malformed() {
+    for text in &[
+        "",
+        "unresolved backtrace v2\nframes 0x10\n",
+        "unresolved backtrace v1\n",
+        "unresolved backtrace v1\nframes 0+0x10\n",
+        "unresolved backtrace v1\nmodule xyz /bin/app\nframes 0+0x10\n",
+        "unresolved backtrace v1\nmodule - /bin/app\nframes 0+10\n",
+    ] {
+        assert!(text.parse::<UnresolvedBacktrace>().is_err(), "{:?}", text);
+    }
+}